@@ -1,7 +1,6 @@
 //! An example to show how to use TcpStream.
 
-use kunio::net::{TcpListener, TcpStream};
-use kunio::runtime::{Runtime, spawn};
+use kunio::runtime::Runtime;
 use kunio::scheduler::LocalScheduler;
 
 fn main() {
@@ -23,11 +22,12 @@ fn run() {
             tx.cancellation().await;
 
             println!("[Client] Server is ready, will connect and send data");
-            let mut conn = TcpStream::connect(ADDRESS)
+            let conn = TcpStream::connect(ADDRESS)
                 .await
                 .expect("[Client] Unable to connect to server");
             let buf: Vec<u8> = vec![97; 10];
-            let (r, _) = conn.write(buf).await.unwrap();
+            let (res, _) = conn.write(buf).await;
+            let r = res.unwrap();
             println!("[Client] Written {} bytes data and leave", r);
         });
     });
@@ -40,14 +40,15 @@ fn run() {
             println!("[Server] Bind ready");
             drop(rx);
 
-            let (mut conn, _addr) = listener
+            let (conn, _addr) = listener
                 .accept()
                 .await
                 .expect("[Server] Unable to accept connection");
             println!("[Server] Accepted a new connection, will read form it");
 
-            let buf = vec![0; 64];
-            let (r, buf) = conn.read(buf).await.unwrap();
+            let buf = Vec::with_capacity(64);
+            let (res, buf) = conn.read(buf).await;
+            let r = res.unwrap();
 
             let read_len = r;
             println!(
@@ -0,0 +1,40 @@
+//! Receiving into kernel-selected buffers from a provided buffer ring.
+
+use kunio::buf::BufRing;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{Runtime, spawn};
+use kunio::scheduler::LocalScheduler;
+
+const ADDRESS: &str = "127.0.0.1:50005";
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let ring = BufRing::new(0, 8, 1024).unwrap();
+        ring.register().expect("failed register buffer ring");
+
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        let server = spawn(async move {
+            let (conn, _) = listener.accept().await.unwrap();
+            for _ in 0..3 {
+                let buf = conn.read_ring(&ring).await.unwrap().unwrap();
+                println!("[Server] got {} bytes in buffer {}", buf.len(), buf.bid());
+                // Echo the ring buffer back; the slot is recycled once it is dropped.
+                conn.write(buf).await.0.unwrap();
+            }
+        });
+
+        let conn = TcpStream::connect(ADDRESS).await.unwrap();
+        for msg in ["hello", "provided", "buffers"] {
+            conn.write(msg.as_bytes().to_vec()).await.0.unwrap();
+            let (res, buf) = conn.read(Vec::with_capacity(64)).await;
+            let n = res.unwrap();
+            println!(
+                "[Client] echoed {} bytes: {:?}",
+                n,
+                String::from_utf8_lossy(&buf)
+            );
+        }
+        server.await;
+    });
+}
@@ -0,0 +1,46 @@
+//! Writing from the buffer types kunio accepts besides `Vec<u8>`, and reading the
+//! file back into a `BytesMut`.
+
+use std::mem::ManuallyDrop;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use kunio::fs::File;
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let file = File::create("bufs.txt").await.unwrap();
+        file.write_all(b"literal, ").await.0.unwrap();
+        file.write_all("str, ").await.0.unwrap();
+        file.write_all(String::from("string, ")).await.0.unwrap();
+        file.write_all(Box::new(*b"array, ")).await.0.unwrap();
+        file.write_all(Rc::<[u8]>::from(&b"rc, "[..]))
+            .await
+            .0
+            .unwrap();
+        file.write_all(Arc::<[u8]>::from(&b"arc, "[..]))
+            .await
+            .0
+            .unwrap();
+        let (res, buf) = file
+            .write_all(ManuallyDrop::new(b"manual\n".to_vec()))
+            .await;
+        res.unwrap();
+        drop(ManuallyDrop::into_inner(buf));
+        file.close().await.unwrap();
+
+        let file = File::open("bufs.txt").await.unwrap();
+        let (res, buf) = file.read_exact(BytesMut::with_capacity(9)).await;
+        res.unwrap();
+        println!("first bytes: {:?}", buf.freeze());
+        let (res, buf) = file.read_to_string(String::new()).await;
+        res.unwrap();
+        print!("rest: {buf}");
+        file.close().await.unwrap();
+    });
+    std::fs::remove_file("bufs.txt").unwrap();
+}
@@ -0,0 +1,47 @@
+//! Linked SQE chains: a write followed by a read of the same data, and a chain
+//! broken by a failing op.
+
+use kunio::driver::OpChain;
+use kunio::driver::op::Op;
+use kunio::fs::File;
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let file = File::create("chain.txt").await.unwrap();
+        let fd = file.as_uring_fd();
+
+        let (write, read) = OpChain::new()
+            .submit(|| {
+                Ok((
+                    Op::write_at(fd, b"linked!".to_vec(), 0),
+                    Op::read_at(fd, Vec::with_capacity(16), 0),
+                ))
+            })
+            .unwrap();
+        let (write, read) = (write.await, read.await);
+        println!("write: {:?}", write.result);
+        println!("read: {:?}", read.result);
+
+        // Reading from a bad fd breaks the chain, the write never runs.
+        let (read, write) = OpChain::new()
+            .submit(|| {
+                Ok((
+                    Op::read(-1, Vec::with_capacity(16)),
+                    Op::write_at(fd, b"never".to_vec(), 0),
+                ))
+            })
+            .unwrap();
+        let (read, write) = (read.await, write.await);
+        println!("read: {:?}", read.result);
+        println!(
+            "write: {:?}, canceled: {}",
+            write.result,
+            write.is_canceled()
+        );
+
+        file.close().await.unwrap();
+    });
+}
@@ -0,0 +1,51 @@
+//! Files and streams close their fd when dropped, and hand it over to std types
+//! through the std fd traits.
+
+use std::io::Read;
+use std::os::fd::{AsFd, FromRawFd, IntoRawFd};
+
+use kunio::fs::File;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{Runtime, spawn};
+use kunio::scheduler::LocalScheduler;
+
+fn open_fds() -> usize {
+    std::fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let file = File::create("close.txt").await.unwrap();
+        file.write_all(b"hello close\n").await.0.unwrap();
+        file.close().await.unwrap();
+
+        let before = open_fds();
+        for _ in 0..100 {
+            let _file = File::open("close.txt").await.unwrap();
+        }
+        // Dropped files are closed by tasks, give their ops a few rounds to complete.
+        for _ in 0..3 {
+            spawn(async {}).await;
+        }
+        println!("fds leaked by 100 dropped files: {}", open_fds() - before);
+
+        let file = File::open("close.txt").await.unwrap();
+        let borrowed = file.as_fd().try_clone_to_owned().unwrap();
+        let fd = file.into_raw_fd();
+        let mut std_file = unsafe { std::fs::File::from_raw_fd(fd) };
+        let mut text = String::new();
+        std_file.read_to_string(&mut text).unwrap();
+        print!("read through std: {text}");
+        drop(borrowed);
+
+        let listener = TcpListener::bind("127.0.0.1:50011").unwrap();
+        let std_stream = std::net::TcpStream::connect("127.0.0.1:50011").unwrap();
+        let stream = unsafe { TcpStream::from_raw_fd(std_stream.into_raw_fd()) };
+        let (accepted, _) = listener.accept().await.unwrap();
+        stream.close().await.unwrap();
+        let (res, _) = accepted.read(Vec::with_capacity(16)).await;
+        println!("peer closed: read {} bytes", res.unwrap());
+    });
+    std::fs::remove_file("close.txt").unwrap();
+}
@@ -0,0 +1,56 @@
+//! An op kunio does not ship, defined and submitted from outside of the crate:
+//! a timeout, once canceled and once dropped before it fires.
+
+use std::time::{Duration, Instant};
+
+use io_uring::{opcode, squeue, types};
+use kunio::driver::UringConfig;
+use kunio::driver::op::{Op, UringOp};
+use kunio::runtime::{DriverKind, Runtime};
+
+struct Timeout {
+    // Boxed, the op moves after building its SQE.
+    timespec: Box<types::Timespec>,
+}
+
+impl Timeout {
+    fn new(duration: Duration) -> Self {
+        Self {
+            timespec: Box::new(types::Timespec::from(duration)),
+        }
+    }
+}
+
+impl UringOp for Timeout {
+    fn build_sqe(&mut self) -> squeue::Entry {
+        opcode::Timeout::new(&*self.timespec).build()
+    }
+}
+
+fn main() {
+    let runtime = Runtime::builder()
+        .driver(DriverKind::Uring)
+        .uring_config(UringConfig::new().big_cqe(true))
+        .build()
+        .expect("failed create runtime");
+
+    runtime.block_on(async {
+        let start = Instant::now();
+        let completion = Op::submit(Timeout::new(Duration::from_millis(20))).await;
+        println!(
+            "timeout after {:?}: {:?}, big cqe {:?}",
+            start.elapsed(),
+            completion.result,
+            completion.big_cqe
+        );
+
+        let op = Op::submit(Timeout::new(Duration::from_secs(10)));
+        op.cancel().unwrap();
+        let completion = op.await;
+        println!("canceled: {}", completion.is_canceled());
+
+        // The driver keeps the timespec alive and cancels the op.
+        drop(Op::submit(Timeout::new(Duration::from_secs(10))));
+        println!("dropped after {:?}", start.elapsed());
+    });
+}
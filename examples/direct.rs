@@ -0,0 +1,42 @@
+//! `O_DIRECT` I/O with buffers aligned to the logical block size. A misaligned
+//! write is refused before it reaches the kernel.
+
+use kunio::buf::AlignedBuf;
+use kunio::fs::OpenOptions;
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .direct(true)
+            .open("direct.txt")
+            .await
+            .unwrap();
+        let block_size = file.logical_block_size().await.unwrap() as usize;
+        println!("logical block size: {block_size}");
+
+        let mut buf = AlignedBuf::new(block_size, block_size);
+        buf.put_slice(b"hello direct\n");
+        buf.zero_fill(block_size);
+        let (res, _) = file.write_at(buf, 0).await;
+        println!("aligned write: {} bytes", res.unwrap());
+
+        let (res, _) = file.write_at(b"misaligned\n".to_vec(), 0).await;
+        println!("misaligned write: {}", res.unwrap_err());
+
+        let (res, buf) = file
+            .read_at(AlignedBuf::new(block_size, block_size), 0)
+            .await;
+        let n = res.unwrap();
+        let text = buf.split(|&b| b == 0).next().unwrap();
+        println!("read {n} bytes: {:?}", String::from_utf8_lossy(text));
+        file.close().await.unwrap();
+    });
+    std::fs::remove_file("direct.txt").unwrap();
+}
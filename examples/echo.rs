@@ -3,6 +3,7 @@
 //! Run the example and `nc 127.0.0.1 50002` in another shell.
 //! All your input will be echoed out.
 
+use kunio::buf::BufPool;
 use kunio::net::{TcpListener, TcpStream};
 use kunio::runtime::{Runtime, spawn};
 use kunio::scheduler::LocalScheduler;
@@ -29,14 +30,18 @@ fn main() {
 }
 
 async fn echo(stream: TcpStream) -> std::io::Result<()> {
-    let mut buf: Vec<u8> = vec![0; 4096];
+    // Connections come and go, their buffers stay in the runtime's pool.
+    let mut buf = BufPool::current().get(4096);
     let mut res;
     loop {
-        (res, buf) = stream.read(buf).await?;
-        if res == 0 {
+        (res, buf) = stream.read(buf).await;
+        if res? == 0 {
             return Ok(());
         }
 
-        (res, buf) = stream.write(buf).await?;
+        // `read` appended what was received to the empty buffer.
+        (res, buf) = stream.write_all(buf).await;
+        res?;
+        buf.clear();
     }
 }
@@ -0,0 +1,57 @@
+//! The same TCP and file ops on the epoll driver, as used when io_uring is not
+//! available.
+
+use kunio::driver::OpChain;
+use kunio::driver::op::Op;
+use kunio::fs::File;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{DriverKind, Runtime, spawn};
+
+const ADDRESS: &str = "127.0.0.1:50008";
+
+fn main() {
+    let runtime = Runtime::builder()
+        .driver(DriverKind::Epoll)
+        .build()
+        .expect("failed create runtime");
+    println!("io_uring driver: {}", runtime.driver.is_uring());
+
+    runtime.block_on(async {
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        spawn(async move {
+            let (stream, addr) = listener.accept().await.unwrap();
+            println!("[server] accepted {addr}");
+            let (res, buf) = stream.read(Vec::with_capacity(64)).await;
+            let n = res.unwrap();
+            stream.write(buf[..n].to_vec()).await.0.unwrap();
+        });
+
+        let stream = TcpStream::connect(ADDRESS).await.unwrap();
+        stream.write(b"hello epoll".to_vec()).await.0.unwrap();
+        let (res, buf) = stream.read(Vec::with_capacity(64)).await;
+        let n = res.unwrap();
+        println!("[client] echoed: {}", String::from_utf8_lossy(&buf[..n]));
+
+        let file = File::create("epoll.txt").await.unwrap();
+        let (res, _) = file.write_at(b"hello file".to_vec(), 0).await;
+        let n = res.unwrap();
+        println!("[file] wrote {n} bytes");
+        let (res, buf) = file.read_at(Vec::with_capacity(64), 0).await;
+        let n = res.unwrap();
+        println!("[file] read: {}", String::from_utf8_lossy(&buf[..n]));
+
+        let fd = file.as_uring_fd();
+        let (write, read) = OpChain::new()
+            .submit(|| {
+                Ok((
+                    Op::write_at(fd, b"linked!".to_vec(), 0),
+                    Op::read_at(fd, Vec::with_capacity(16), 0),
+                ))
+            })
+            .unwrap();
+        let (write, read) = (write.await, read.await);
+        println!("[chain] write: {:?}, read: {:?}", write.result, read.result);
+
+        file.close().await.unwrap();
+    });
+}
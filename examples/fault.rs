@@ -0,0 +1,56 @@
+//! Faults injected into real io_uring ops: a short write, an interrupted read and
+//! a delayed completion.
+
+use std::time::{Duration, Instant};
+
+use io_uring::opcode;
+use kunio::buf::IoBuf;
+use kunio::driver::{Fault, FaultRule};
+use kunio::fs::File;
+use kunio::runtime::{DriverKind, Runtime};
+
+fn main() {
+    let runtime = Runtime::builder()
+        .driver(DriverKind::Uring)
+        .build()
+        .expect("failed create runtime");
+    let driver = runtime.driver.uring().unwrap();
+    driver.inject(
+        FaultRule::new(Fault::Short(5))
+            .opcode(opcode::Write::CODE)
+            .times(1),
+    );
+    driver.inject(
+        FaultRule::new(Fault::Error(libc::EINTR))
+            .opcode(opcode::Read::CODE)
+            .times(1),
+    );
+    driver.inject(
+        FaultRule::new(Fault::Delay(Duration::from_millis(50)))
+            .opcode(opcode::Read::CODE)
+            .times(2),
+    );
+
+    runtime.block_on(async {
+        let file = File::create("fault.txt").await.unwrap();
+        let (res, buf) = file.write_at(b"hello fault".to_vec(), 0).await;
+        let n = res.unwrap();
+        println!("short write: {n} bytes");
+        let (res, _) = file.write_at(buf.slice(n..), n as u64).await;
+        println!("rest: {} bytes", res.unwrap());
+
+        let err = file.read_at(Vec::with_capacity(32), 0).await.0.err();
+        println!("interrupted read: {err:?}");
+
+        let start = Instant::now();
+        let (res, buf) = file.read_at(Vec::with_capacity(32), 0).await;
+        let n = res.unwrap();
+        println!(
+            "delayed read after {:?}: {}",
+            start.elapsed(),
+            String::from_utf8_lossy(&buf[..n])
+        );
+        file.close().await.unwrap();
+    });
+    std::fs::remove_file("fault.txt").unwrap();
+}
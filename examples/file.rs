@@ -7,14 +7,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let future = async {
         let file = File::create("foo.txt").await?;
         let buf: Vec<u8> = b"hello kunio!\nlove sing dance basketball!!!".to_vec();
-        let (res, buf) = file.write(buf).await?;
+        let (res, buf) = file.write(buf).await;
+        let res = res?;
 
         println!("wrote {} bytes {:?}", res, buf);
 
         let join_handle = spawn(async {
             let file = File::open("foo.txt").await?;
-            let buf: Vec<u8> = vec![0; 12];
-            let (res, buf) = file.read(buf).await?;
+            let buf: Vec<u8> = Vec::with_capacity(12);
+            let (res, buf) = file.read(buf).await;
+            let res = res?;
             println!("read {} bytes {:?}", res, buf);
             Ok::<(), Box<dyn std::error::Error>>(())
         });
@@ -0,0 +1,46 @@
+//! Registered (fixed) file descriptors.
+
+use kunio::fs::{File, FixedFile};
+use kunio::net::{FixedTcpStream, TcpListener};
+use kunio::runtime::{Runtime, spawn};
+use kunio::scheduler::LocalScheduler;
+
+const ADDRESS: &str = "127.0.0.1:50003";
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime
+        .driver
+        .uring()
+        .and_then(|driver| driver.register_files_sparse(16))
+        .expect("failed register file table");
+
+    runtime.block_on(async {
+        let file = File::create("fixed.txt").await.unwrap();
+        let file = file.into_fixed().unwrap();
+        let (res, _) = file.write(b"hello fixed file\n".to_vec()).await;
+        let n = res.unwrap();
+        println!("wrote {} bytes through a fixed slot", n);
+        file.close().await.unwrap();
+
+        let file = FixedFile::open("fixed.txt").await.unwrap();
+        let (res, buf) = file.read(Vec::with_capacity(64)).await;
+        let n = res.unwrap();
+        println!("read {} bytes: {:?}", n, String::from_utf8_lossy(&buf));
+        file.close().await.unwrap();
+
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        let server = spawn(async move {
+            let (conn, addr) = listener.accept_fixed().await.unwrap();
+            let (res, buf) = conn.read(Vec::with_capacity(64)).await;
+            let n = res.unwrap();
+            println!("[Server] read {} bytes from {}: {:?}", n, addr, buf);
+        });
+
+        let conn = FixedTcpStream::connect(ADDRESS).await.unwrap();
+        let (res, _) = conn.write(b"ping".to_vec()).await;
+        let n = res.unwrap();
+        println!("[Client] wrote {} bytes", n);
+        server.await;
+    });
+}
@@ -0,0 +1,49 @@
+//! Registered (fixed) buffers, used by `ReadFixed`/`WriteFixed` under the hood.
+
+use kunio::buf::FixedBufPool;
+use kunio::fs::File;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{Runtime, spawn};
+use kunio::scheduler::LocalScheduler;
+
+const ADDRESS: &str = "127.0.0.1:50004";
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let pool = FixedBufPool::new((0..4).map(|_| Vec::with_capacity(4096)));
+        pool.register().expect("failed register buffers");
+
+        let file = File::create("fixed_buf.txt").await.unwrap();
+        let mut buf = pool.try_next(4096).unwrap();
+        buf.put_slice(b"hello fixed buffer\n");
+        let (res, mut buf) = file.write_at(buf, 0).await;
+        let n = res.unwrap();
+        println!("wrote {} bytes from a fixed buffer", n);
+
+        buf.clear();
+        let (res, buf) = file.read_at(buf, 0).await;
+        let n = res.unwrap();
+        println!(
+            "read {} bytes into a fixed buffer: {:?}",
+            n,
+            String::from_utf8_lossy(&buf)
+        );
+        file.close().await.unwrap();
+
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        let read_buf = pool.try_next(64).unwrap();
+        let server = spawn(async move {
+            let (conn, _) = listener.accept().await.unwrap();
+            let (res, buf) = conn.read(read_buf).await;
+            let n = res.unwrap();
+            println!("[Server] read {} bytes: {:?}", n, &buf[..]);
+        });
+
+        let conn = TcpStream::connect(ADDRESS).await.unwrap();
+        let (res, _) = conn.write(buf).await;
+        let n = res.unwrap();
+        println!("[Client] wrote {} bytes from a fixed buffer", n);
+        server.await;
+    });
+}
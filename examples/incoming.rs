@@ -0,0 +1,38 @@
+//! Accepting connections with a single multishot accept SQE.
+
+use futures::StreamExt;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{Runtime, spawn};
+use kunio::scheduler::LocalScheduler;
+
+const ADDRESS: &str = "127.0.0.1:50006";
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        let server = spawn(async move {
+            let incoming = listener.incoming().unwrap();
+            let mut incoming = incoming.take(3);
+            while let Some(conn) = incoming.next().await {
+                let conn = conn.unwrap();
+                let (res, buf) = conn.read(Vec::with_capacity(64)).await;
+                let n = res.unwrap();
+                println!(
+                    "[Server] read {} bytes: {:?}",
+                    n,
+                    String::from_utf8_lossy(&buf)
+                );
+            }
+        });
+
+        for i in 0..3 {
+            let conn = TcpStream::connect(ADDRESS).await.unwrap();
+            conn.write(format!("client {i}").into_bytes())
+                .await
+                .0
+                .unwrap();
+        }
+        server.await;
+    });
+}
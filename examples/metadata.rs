@@ -0,0 +1,49 @@
+//! Inspecting files with statx: the metadata of an open file, of a path, and of a
+//! symlink without following it.
+
+use kunio::fs::{self, File};
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+use std::os::unix::fs::PermissionsExt;
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let file = File::create("metadata.txt").await.unwrap();
+        let (res, _) = file.write_at(b"hello statx\n".to_vec(), 0).await;
+        res.unwrap();
+
+        let meta = file.metadata().await.unwrap();
+        println!("{meta:?}");
+        println!(
+            "len: {}, is_file: {}, mode: {:o}, ino: {}, blksize: {}",
+            meta.len(),
+            meta.is_file(),
+            meta.permissions().mode(),
+            meta.ino(),
+            meta.blksize()
+        );
+        println!("modified: {:?}", meta.modified());
+        println!("created: {:?}", meta.created());
+        file.close().await.unwrap();
+
+        std::os::unix::fs::symlink("metadata.txt", "metadata-link.txt").unwrap();
+        let target = fs::metadata("metadata-link.txt").await.unwrap();
+        let link = fs::symlink_metadata("metadata-link.txt").await.unwrap();
+        println!(
+            "through the link: is_file: {}, len: {}",
+            target.is_file(),
+            target.len()
+        );
+        println!(
+            "the link itself: is_symlink: {}, len: {}",
+            link.is_symlink(),
+            link.len()
+        );
+
+        let missing = fs::metadata("missing.txt").await.unwrap_err();
+        println!("missing file: {:?}", missing.kind());
+    });
+    std::fs::remove_file("metadata-link.txt").unwrap();
+    std::fs::remove_file("metadata.txt").unwrap();
+}
@@ -0,0 +1,69 @@
+//! A request/response exchange on the in-memory mock driver. The interleaving
+//! depends only on the seed, and faults are injected into the client's writes.
+//!
+//! Run with a seed, e.g. `cargo run --example mock -- 42`.
+
+use std::os::fd::FromRawFd;
+use std::time::Duration;
+
+use io_uring::opcode;
+use kunio::driver::{Fault, FaultRule};
+use kunio::fs::File;
+use kunio::net::TcpStream;
+use kunio::runtime::{DriverKind, Runtime, spawn};
+
+fn main() {
+    let seed = std::env::args()
+        .nth(1)
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0);
+    let runtime = Runtime::builder()
+        .driver(DriverKind::Mock { seed })
+        .build()
+        .expect("failed create runtime");
+    let mock = runtime.driver.mock().unwrap();
+    mock.latency(Duration::from_millis(10));
+    mock.add_file("greeting.txt", "hello mock");
+
+    let (client_fd, server_fd) = mock.stream_pair();
+    // The first write is cut short, the second fails.
+    mock.inject(
+        FaultRule::new(Fault::Short(4))
+            .opcode(opcode::Send::CODE)
+            .fd(client_fd)
+            .times(1),
+    );
+    mock.inject(
+        FaultRule::new(Fault::Error(libc::ECONNRESET))
+            .opcode(opcode::Send::CODE)
+            .fd(client_fd)
+            .times(1),
+    );
+
+    runtime.block_on(async move {
+        let client = unsafe { TcpStream::from_raw_fd(client_fd) };
+        let server = unsafe { TcpStream::from_raw_fd(server_fd) };
+
+        for i in 0..3 {
+            spawn(async move {
+                let file = File::open("greeting.txt").await.unwrap();
+                let (res, buf) = file.read_at(Vec::with_capacity(32), 0).await;
+                let n = res.unwrap();
+                println!("[reader {i}] {}", String::from_utf8_lossy(&buf[..n]));
+            });
+        }
+
+        let (res, _) = client.write(b"ping ping".to_vec()).await;
+        let n = res.unwrap();
+        println!("[client] first write: {n} bytes");
+        println!(
+            "[client] second write: {:?}",
+            client.write(b"ping".to_vec()).await.0.err()
+        );
+
+        let (res, buf) = server.read(Vec::with_capacity(32)).await;
+        let n = res.unwrap();
+        println!("[server] read {:?}", String::from_utf8_lossy(&buf[..n]));
+    });
+    println!("virtual time: {:?}", runtime.driver.mock().unwrap().now());
+}
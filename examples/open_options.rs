@@ -0,0 +1,48 @@
+//! Opening files with `OpenOptions`: appending, refusing to clobber an existing
+//! file, and `openat2` keeping the path beneath the working directory.
+
+use kunio::fs::{File, OpenOptions};
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let file = File::create("open_options.txt").await.unwrap();
+        file.write_all(b"first\n").await.0.unwrap();
+        file.close().await.unwrap();
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open("open_options.txt")
+            .await
+            .unwrap();
+        file.write_all(b"appended\n").await.0.unwrap();
+        file.close().await.unwrap();
+
+        let err = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open("open_options.txt")
+            .await
+            .err();
+        println!("create_new on an existing file: {err:?}");
+
+        let file = File::open("open_options.txt").await.unwrap();
+        let (res, _) = file.write(b"nope".to_vec()).await;
+        println!("write to a read-only file: {:?}", res.err());
+        let (res, text) = file.read_to_string(String::new()).await;
+        res.unwrap();
+        print!("contents:\n{text}");
+        file.close().await.unwrap();
+
+        let mut beneath = OpenOptions::new();
+        beneath.read(true).resolve(libc::RESOLVE_BENEATH);
+        let file = beneath.open("open_options.txt").await.unwrap();
+        file.close().await.unwrap();
+        println!("beneath: opened open_options.txt");
+        let err = beneath.open("/etc/hostname").await.err();
+        println!("beneath: /etc/hostname: {err:?}");
+    });
+    std::fs::remove_file("open_options.txt").unwrap();
+}
@@ -0,0 +1,46 @@
+//! The runtime's buffer pool, with a few of its buffers registered as fixed
+//! buffers. Dropped buffers go back to the pool and are handed out again.
+
+use kunio::buf::{BufPool, BufPoolConfig, IoBuf};
+use kunio::fs::File;
+use kunio::runtime::{DriverKind, Runtime, spawn};
+
+fn main() {
+    let runtime = Runtime::builder()
+        .driver(DriverKind::Uring)
+        .buf_pool(BufPoolConfig::new().size_classes([64, 4096]).fixed(2))
+        .build()
+        .expect("failed create runtime");
+    runtime.block_on(async {
+        let file = File::create("pool.txt").await.unwrap();
+        let mut buf = BufPool::current().get(32);
+        buf.put_slice(b"hello pool\n");
+        println!("write from fixed buffer {:?}", buf.buf_index());
+        file.write_all(buf).await.0.unwrap();
+
+        let mut handles = Vec::new();
+        for i in 0..4 {
+            handles.push(spawn(async move {
+                let file = File::open("pool.txt").await.unwrap();
+                let (res, buf) = file.read_at(BufPool::current().get(4096), 0).await;
+                res.unwrap();
+                println!(
+                    "[reader {i}] {:?} in a {} byte buffer, fixed {:?}",
+                    String::from_utf8_lossy(&buf),
+                    buf.capacity(),
+                    buf.buf_index()
+                );
+            }));
+        }
+        for handle in handles {
+            handle.await;
+        }
+
+        let big = BufPool::current().get(1 << 20);
+        println!("unpooled buffer of {} bytes", big.capacity());
+        drop(big);
+        file.close().await.unwrap();
+        println!("{:?}", BufPool::current().stats());
+    });
+    std::fs::remove_file("pool.txt").unwrap();
+}
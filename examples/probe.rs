@@ -0,0 +1,42 @@
+//! Which opcodes the driver supports on this kernel. `TcpStream::connect` works
+//! either way: without IORING_OP_SOCKET the socket comes from `libc::socket`, and
+//! file ops the kernel lacks run on a blocking pool.
+
+use io_uring::opcode;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{Runtime, spawn};
+
+const ADDRESS: &str = "127.0.0.1:50009";
+
+fn main() {
+    let runtime = Runtime::builder().build().expect("failed create runtime");
+    println!("io_uring driver: {}", runtime.driver.is_uring());
+    for (name, code) in [
+        ("READ", opcode::Read::CODE),
+        ("WRITE", opcode::Write::CODE),
+        ("OPENAT", opcode::OpenAt::CODE),
+        ("CLOSE", opcode::Close::CODE),
+        ("SEND", opcode::Send::CODE),
+        ("RECV", opcode::Recv::CODE),
+        ("SOCKET", opcode::Socket::CODE),
+        ("FTRUNCATE", opcode::Ftruncate::CODE),
+    ] {
+        println!("{name:>10}: {}", runtime.supports(code));
+    }
+
+    runtime.block_on(async {
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (res, buf) = stream.read(Vec::with_capacity(64)).await;
+            let n = res.unwrap();
+            stream.write(buf[..n].to_vec()).await.0.unwrap();
+        });
+
+        let stream = TcpStream::connect(ADDRESS).await.unwrap();
+        stream.write(b"hello probe".to_vec()).await.0.unwrap();
+        let (res, buf) = stream.read(Vec::with_capacity(64)).await;
+        let n = res.unwrap();
+        println!("[client] echoed: {}", String::from_utf8_lossy(&buf[..n]));
+    });
+}
@@ -0,0 +1,49 @@
+//! `write_all`, `read_exact` and `read_to_string` on the mock driver, with every
+//! op cut short so they have to carry on where the last one stopped. A failed
+//! write hands the buffer back, so it can be sent again.
+
+use std::os::fd::FromRawFd;
+
+use io_uring::opcode;
+use kunio::driver::{Fault, FaultRule};
+use kunio::fs::File;
+use kunio::net::TcpStream;
+use kunio::runtime::{DriverKind, Runtime, spawn};
+
+fn main() {
+    let runtime = Runtime::builder()
+        .driver(DriverKind::Mock { seed: 7 })
+        .build()
+        .expect("failed create runtime");
+    let mock = runtime.driver.mock().unwrap();
+    mock.add_file("poem.txt", "roses are red,\nviolets are blue\n");
+    mock.inject(
+        FaultRule::new(Fault::Error(libc::EAGAIN))
+            .opcode(opcode::Send::CODE)
+            .times(1),
+    );
+    mock.inject(FaultRule::new(Fault::Short(3)).opcode(opcode::Send::CODE));
+    mock.inject(FaultRule::new(Fault::Short(5)).opcode(opcode::Recv::CODE));
+    mock.inject(FaultRule::new(Fault::Short(4)).opcode(opcode::Read::CODE));
+
+    let (client_fd, server_fd) = mock.stream_pair();
+    runtime.block_on(async move {
+        let client = unsafe { TcpStream::from_raw_fd(client_fd) };
+        let server = unsafe { TcpStream::from_raw_fd(server_fd) };
+
+        spawn(async move {
+            let (res, buf) = server.read_exact(Vec::with_capacity(11)).await;
+            res.unwrap();
+            println!("[server] read_exact: {}", String::from_utf8_lossy(&buf));
+        });
+        let (res, buf) = client.write_all(b"hello world".to_vec()).await;
+        println!("[client] write_all: {:?}", res.err());
+        let (res, _) = client.write_all(buf).await;
+        println!("[client] write_all again: {:?}", res);
+
+        let file = File::open("poem.txt").await.unwrap();
+        let (res, poem) = file.read_to_string(String::new()).await;
+        let n = res.unwrap();
+        print!("[file] read_to_string {n} bytes:\n{poem}");
+    });
+}
@@ -0,0 +1,40 @@
+//! Receiving a stream of chunks from a single multishot recv SQE.
+
+use futures::StreamExt;
+use kunio::buf::BufRing;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{Runtime, spawn};
+use kunio::scheduler::LocalScheduler;
+
+const ADDRESS: &str = "127.0.0.1:50007";
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let ring = BufRing::new(1, 2, 16).unwrap();
+        ring.register().expect("failed register buffer ring");
+
+        let listener = TcpListener::bind(ADDRESS).unwrap();
+        let server = spawn(async move {
+            let (conn, _) = listener.accept().await.unwrap();
+            let mut chunks = conn.recv_stream(&ring).unwrap();
+            let mut total = 0;
+            while total < 64 {
+                let buf = chunks.next().await.unwrap().unwrap();
+                total += buf.len();
+                println!(
+                    "[Server] chunk of {} bytes in buffer {}",
+                    buf.len(),
+                    buf.bid()
+                );
+            }
+            println!("[Server] received {} bytes", total);
+        });
+
+        let conn = TcpStream::connect(ADDRESS).await.unwrap();
+        for _ in 0..4 {
+            conn.write(vec![b'k'; 16]).await.0.unwrap();
+        }
+        server.await;
+    });
+}
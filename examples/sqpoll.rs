@@ -0,0 +1,35 @@
+//! Two runtimes sharing one SQPOLL kernel thread.
+
+use std::os::fd::AsRawFd;
+
+use kunio::driver::UringConfig;
+use kunio::fs::File;
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+
+fn main() {
+    let config = UringConfig::new().sqpoll(2000);
+    let runtime = Runtime::new_with_config(Box::new(LocalScheduler), 0, &config)
+        .expect("failed create runtime");
+    let ring_fd = runtime.driver.uring().unwrap().as_raw_fd();
+
+    // Runtimes are built on one thread, as creating one is not synchronized with
+    // another running elsewhere.
+    let config = UringConfig::new().sqpoll(2000).attach_wq(ring_fd);
+    let attached = Runtime::new_with_config(Box::new(LocalScheduler), 0, &config)
+        .expect("failed create attached runtime");
+
+    runtime.block_on(async {
+        let file = File::create("sqpoll.txt").await.unwrap();
+        let (res, _) = file.write(b"hello sqpoll\n".to_vec()).await;
+        let n = res.unwrap();
+        println!("[main] wrote {} bytes", n);
+    });
+
+    attached.block_on(async {
+        let file = File::create("sqpoll_attached.txt").await.unwrap();
+        let (res, _) = file.write(b"attached\n".to_vec()).await;
+        let n = res.unwrap();
+        println!("[attached] wrote {} bytes", n);
+    });
+}
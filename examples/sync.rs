@@ -0,0 +1,46 @@
+//! Durability and space management for a write-ahead log: preallocating the log,
+//! syncing appended records, and trimming and punching out what was checkpointed.
+
+use kunio::fs::File;
+use kunio::runtime::Runtime;
+use kunio::scheduler::LocalScheduler;
+
+fn size(path: &str) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+fn main() {
+    let runtime = Runtime::new(Box::new(LocalScheduler), 0).expect("failed create runtime");
+    runtime.block_on(async {
+        let log = File::create("wal.txt").await.unwrap();
+        log.allocate(0, 1 << 20).await.unwrap();
+        println!("preallocated: {} bytes", size("wal.txt"));
+        log.set_len(0).await.unwrap();
+
+        let mut offset = 0;
+        for i in 0..3 {
+            let record = format!("record {i}\n").into_bytes();
+            let (res, _) = log.write_at(record, offset).await;
+            offset += res.unwrap() as u64;
+            log.sync_data().await.unwrap();
+        }
+        log.sync_range(0, 0, libc::SYNC_FILE_RANGE_WRITE)
+            .await
+            .unwrap();
+        log.sync_all().await.unwrap();
+        println!("synced {offset} bytes of records");
+
+        log.punch_hole(0, 9).await.unwrap();
+        let (res, buf) = log.read_at(Vec::with_capacity(64), 0).await;
+        res.unwrap();
+        println!(
+            "after punching out record 0: {:?}",
+            String::from_utf8_lossy(&buf)
+        );
+
+        log.set_len(18).await.unwrap();
+        println!("truncated to {} bytes", size("wal.txt"));
+        log.close().await.unwrap();
+    });
+    std::fs::remove_file("wal.txt").unwrap();
+}
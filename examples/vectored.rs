@@ -0,0 +1,59 @@
+//! Header plus body without copying them into one buffer, on a file and a socket.
+
+use kunio::fs::File;
+use kunio::net::{TcpListener, TcpStream};
+use kunio::runtime::{DriverKind, Runtime, spawn};
+
+const ADDRESS: &str = "127.0.0.1:50010";
+
+fn main() {
+    for driver in [DriverKind::Uring, DriverKind::Epoll] {
+        println!("{driver:?}");
+        let runtime = Runtime::builder()
+            .driver(driver)
+            .build()
+            .expect("failed create runtime");
+        runtime.block_on(run());
+    }
+}
+
+async fn run() {
+    let file = File::create("vectored.txt").await.unwrap();
+    let header = b"header:".to_vec();
+    let (res, _) = file
+        .write_vectored_at((header, b"body" as &'static [u8]), 0)
+        .await;
+    let n = res.unwrap();
+    println!("[file] wrote {n} bytes");
+    let (res, (head, body)) = file
+        .read_vectored_at((Vec::with_capacity(7), Vec::with_capacity(16)), 0)
+        .await;
+    let n = res.unwrap();
+    println!(
+        "[file] read {n} bytes: {:?} {:?}",
+        String::from_utf8_lossy(&head),
+        String::from_utf8_lossy(&body)
+    );
+    file.close().await.unwrap();
+
+    let listener = TcpListener::bind(ADDRESS).unwrap();
+    spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (res, bufs) = stream
+            .read_vectored(vec![Vec::with_capacity(4), Vec::with_capacity(64)])
+            .await;
+        let n = res.unwrap();
+        println!("[server] received {n} bytes in {:?}", bufs);
+        stream.write_vectored(bufs).await.0.unwrap();
+    });
+
+    let stream = TcpStream::connect(ADDRESS).await.unwrap();
+    stream
+        .write_vectored([b"ping".to_vec(), b" pong".to_vec()])
+        .await
+        .0
+        .unwrap();
+    let (res, buf) = stream.read(Vec::with_capacity(64)).await;
+    let n = res.unwrap();
+    println!("[client] echoed: {}", String::from_utf8_lossy(&buf[..n]));
+}
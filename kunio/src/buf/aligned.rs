@@ -0,0 +1,137 @@
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::buf::{IoBuf, IoBufMut};
+
+/// Heap memory aligned to `align`, as `O_DIRECT` wants it for the address and the
+/// length of every transfer.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+impl AlignedBuf {
+    /// An empty buffer of `capacity` bytes, rounded up to a multiple of `align`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two or the size overflows.
+    pub fn new(capacity: usize, align: usize) -> Self {
+        let layout = Layout::from_size_align(capacity.max(1), align)
+            .expect("invalid buffer alignment")
+            .pad_to_align();
+        // Safety: the size is not zero.
+        let ptr = unsafe { alloc::alloc(layout) };
+        let Some(ptr) = NonNull::new(ptr) else {
+            alloc::handle_alloc_error(layout);
+        };
+        Self {
+            ptr,
+            len: 0,
+            layout,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Shorten the buffer to `len` bytes, if it is longer.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Append `src` to the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` does not fit in the remaining capacity.
+    pub fn put_slice(&mut self, src: &[u8]) {
+        assert!(
+            src.len() <= self.capacity() - self.len,
+            "aligned buffer overflow"
+        );
+        // Safety: checked to be within the allocation.
+        unsafe {
+            std::ptr::copy_nonoverlapping(src.as_ptr(), self.ptr.as_ptr().add(self.len), src.len())
+        };
+        self.len += src.len();
+    }
+
+    /// Grow the buffer to `len` bytes with zeros, e.g. to pad a write to the block
+    /// size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is larger than the capacity.
+    pub fn zero_fill(&mut self, len: usize) {
+        assert!(len <= self.capacity(), "aligned buffer overflow");
+        if len > self.len {
+            // Safety: checked to be within the allocation.
+            unsafe {
+                self.ptr
+                    .as_ptr()
+                    .add(self.len)
+                    .write_bytes(0, len - self.len)
+            };
+            self.len = len;
+        }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        // Safety: allocated with this layout in `new`.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the first `len` bytes are initialized.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // Safety: the first `len` bytes are initialized.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl IoBuf for AlignedBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl IoBufMut for AlignedBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.len = self.len.max(pos);
+    }
+}
@@ -0,0 +1,270 @@
+use std::cell::RefCell;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::buf::{IoBuf, IoBufMut};
+use crate::runtime::RUNTIME;
+
+/// Buffers registered with `io_uring_register_buffers`.
+///
+/// A slot is either home (`Some`) or checked out as a `FixedBuf`, which puts the
+/// `Vec` back on drop. Moving the `Vec` around never moves its heap memory, so the
+/// registered iovecs stay valid.
+struct FixedBuffers {
+    bufs: Vec<Option<Vec<u8>>>,
+    /// The id of the runtime whose ring the buffers are registered with.
+    registered: Option<u32>,
+}
+
+impl FixedBuffers {
+    fn new(bufs: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        Self {
+            bufs: bufs.into_iter().map(Some).collect(),
+            registered: None,
+        }
+    }
+
+    fn register(&mut self) -> io::Result<()> {
+        if self.bufs.len() > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many fixed buffers",
+            ));
+        }
+        let iovecs = self
+            .bufs
+            .iter_mut()
+            .map(|buf| match buf {
+                Some(buf) => Ok(libc::iovec {
+                    iov_base: buf.as_mut_ptr() as *mut _,
+                    iov_len: buf.capacity(),
+                }),
+                None => Err(io::Error::new(
+                    io::ErrorKind::ResourceBusy,
+                    "fixed buffer is checked out",
+                )),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        // Safety: the buffers are owned by `self` (or a `FixedBuf` of it) until they
+        // are unregistered.
+        RUNTIME.with(|runtime| unsafe { runtime.driver.uring()?.register_buffers(&iovecs) })?;
+        self.registered = Some(RUNTIME.with(|runtime| runtime.id));
+        Ok(())
+    }
+
+    fn unregister(&mut self) -> io::Result<()> {
+        if self.registered_here() {
+            RUNTIME.with(|runtime| runtime.driver.uring()?.unregister_buffers())?;
+            self.registered = None;
+        }
+        Ok(())
+    }
+
+    /// Whether the buffers are registered with the current runtime's ring, so that
+    /// their indices mean something to it.
+    fn registered_here(&self) -> bool {
+        self.registered
+            .is_some_and(|id| RUNTIME.is_set() && RUNTIME.with(|runtime| runtime.id) == id)
+    }
+
+    fn check_out(&mut self, index: usize, shared: &Rc<RefCell<FixedBuffers>>) -> Option<FixedBuf> {
+        if !self.registered_here() {
+            return None;
+        }
+        let buf = self.bufs.get_mut(index)?.take()?;
+        Some(FixedBuf {
+            buffers: shared.clone(),
+            buf: ManuallyDrop::new(buf),
+            index: index as u16,
+        })
+    }
+}
+
+impl Drop for FixedBuffers {
+    fn drop(&mut self) {
+        if self.registered_here() {
+            let _ = self.unregister();
+        }
+    }
+}
+
+/// A set of registered buffers, checked out by index.
+pub struct FixedBufRegistry {
+    inner: Rc<RefCell<FixedBuffers>>,
+}
+
+impl FixedBufRegistry {
+    pub fn new(bufs: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(FixedBuffers::new(bufs))),
+        }
+    }
+
+    /// Register the buffers with the current runtime's ring.
+    pub fn register(&self) -> io::Result<()> {
+        self.inner.borrow_mut().register()
+    }
+
+    pub fn unregister(&self) -> io::Result<()> {
+        self.inner.borrow_mut().unregister()
+    }
+
+    /// Take the buffer at `index`, or `None` if it is already checked out or the
+    /// buffers are not registered with the current runtime.
+    pub fn check_out(&self, index: usize) -> Option<FixedBuf> {
+        self.inner.borrow_mut().check_out(index, &self.inner)
+    }
+}
+
+/// A set of registered buffers, handing out whichever is free.
+pub struct FixedBufPool {
+    inner: Rc<RefCell<FixedBuffers>>,
+}
+
+impl FixedBufPool {
+    pub fn new(bufs: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(FixedBuffers::new(bufs))),
+        }
+    }
+
+    /// Register the buffers with the current runtime's ring.
+    pub fn register(&self) -> io::Result<()> {
+        self.inner.borrow_mut().register()
+    }
+
+    pub fn unregister(&self) -> io::Result<()> {
+        self.inner.borrow_mut().unregister()
+    }
+
+    /// Take a free buffer with at least `capacity` bytes, `None` if there is none or
+    /// the buffers are not registered with the current runtime.
+    pub fn try_next(&self, capacity: usize) -> Option<FixedBuf> {
+        let mut inner = self.inner.borrow_mut();
+        let index = inner
+            .bufs
+            .iter()
+            .position(|buf| matches!(buf, Some(buf) if buf.capacity() >= capacity))?;
+        inner.check_out(index, &self.inner)
+    }
+}
+
+/// A registered buffer. Ops on it are issued as `ReadFixed`/`WriteFixed`, or as
+/// plain reads and writes once its buffers are unregistered from the current ring.
+pub struct FixedBuf {
+    buffers: Rc<RefCell<FixedBuffers>>,
+    buf: ManuallyDrop<Vec<u8>>,
+    index: u16,
+}
+
+impl FixedBuf {
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Append `src` to the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` does not fit in the remaining capacity, as growing would move
+    /// the data out of the registered memory.
+    pub fn put_slice(&mut self, src: &[u8]) {
+        assert!(
+            src.len() <= self.buf.capacity() - self.buf.len(),
+            "fixed buffer overflow"
+        );
+        self.buf.extend_from_slice(src);
+    }
+}
+
+impl Deref for FixedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for FixedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Drop for FixedBuf {
+    fn drop(&mut self) {
+        // Safety: `buf` is not used after this.
+        let buf = unsafe { ManuallyDrop::take(&mut self.buf) };
+        self.buffers.borrow_mut().bufs[self.index as usize] = Some(buf);
+    }
+}
+
+impl IoBuf for FixedBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.buf.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    fn buf_index(&self) -> Option<u16> {
+        self.buffers
+            .borrow()
+            .registered_here()
+            .then_some(self.index)
+    }
+}
+
+impl IoBufMut for FixedBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.buf.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if pos > self.buf.len() {
+            unsafe { self.buf.set_len(pos) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedBufRegistry;
+    use crate::fs::File;
+    use crate::runtime::{DriverKind, Runtime};
+
+    #[test]
+    fn check_out_needs_registration() {
+        let runtime = Runtime::builder()
+            .driver(DriverKind::Uring)
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let registry = FixedBufRegistry::new([Vec::with_capacity(16)]);
+            assert!(registry.check_out(0).is_none());
+            registry.register().unwrap();
+            let mut buf = registry.check_out(0).unwrap();
+            buf.put_slice(b"hello");
+
+            // Unregistered while checked out, the buffer is written without its index.
+            registry.unregister().unwrap();
+            let file = File::create("fixed_unregistered.txt").await.unwrap();
+            let (res, _) = file.write_at(buf, 0).await;
+            assert_eq!(res.unwrap(), 5);
+            file.close().await.unwrap();
+        });
+        assert_eq!(std::fs::read("fixed_unregistered.txt").unwrap(), b"hello");
+        std::fs::remove_file("fixed_unregistered.txt").unwrap();
+    }
+}
@@ -1,56 +1,289 @@
-pub trait IoBuf {
-    fn read_ptr(&self) -> *const u8;
-    fn valid_len(&self) -> u32;
+use std::mem::ManuallyDrop;
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::Slice;
+
+/// Memory an op reads from. The op owns it until it completes, so it cannot borrow.
+///
+/// The first `bytes_init` bytes hold data, the rest up to `bytes_total` may be
+/// uninitialized. Writes send the initialized bytes. A single op moves at most
+/// about 2 GiB like the kernel does, and fails with `InvalidInput` for larger
+/// buffers; `write_all` and the like split them.
+pub trait IoBuf: 'static {
+    /// Start of the memory, which must not move while the buffer is not moved
+    /// itself, e.g. heap memory.
+    fn stable_ptr(&self) -> *const u8;
+
+    fn bytes_init(&self) -> usize;
+
+    fn bytes_total(&self) -> usize;
+
+    /// Index of the registered buffer this memory belongs to, if any. Ops on such
+    /// buffers are issued as `ReadFixed`/`WriteFixed`.
+    fn buf_index(&self) -> Option<u16> {
+        None
+    }
+
+    /// Take `range` of the buffer, e.g. to write the rest of it after a short
+    /// write.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of `bytes_total`, or starts past `bytes_init`: a read
+    /// into the slice would leave a gap of uninitialized bytes before it.
+    fn slice(self, range: impl RangeBounds<usize>) -> Slice<Self>
+    where
+        Self: Sized,
+    {
+        let total = self.bytes_total();
+        let begin = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => total,
+        };
+        assert!(
+            begin <= end && end <= total,
+            "range {begin}..{end} out of bounds for buffer of {total} bytes"
+        );
+        let init = self.bytes_init();
+        assert!(
+            begin <= init,
+            "range {begin}..{end} starts past the {init} initialized bytes"
+        );
+        Slice::new(self, begin, end)
+    }
 }
 
 impl IoBuf for Vec<u8> {
-    fn read_ptr(&self) -> *const u8 {
+    fn stable_ptr(&self) -> *const u8 {
         self.as_ptr()
     }
 
-    fn valid_len(&self) -> u32 {
-        self.len() as u32
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
     }
 }
 
 impl IoBuf for Box<[u8]> {
-    fn read_ptr(&self) -> *const u8 {
+    fn stable_ptr(&self) -> *const u8 {
         self.as_ptr()
     }
 
-    fn valid_len(&self) -> u32 {
-        self.len() as u32
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
     }
 }
 
 impl IoBuf for &'static [u8] {
-    fn read_ptr(&self) -> *const u8 {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+// Bare `[u8; N]` arrays are left out on purpose: they live inside the op, which
+// moves after the SQE pointing at them was pushed. Box them, or use literals.
+impl<const N: usize> IoBuf for &'static [u8; N] {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        N
+    }
+
+    fn bytes_total(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> IoBuf for Box<[u8; N]> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        N
+    }
+
+    fn bytes_total(&self) -> usize {
+        N
+    }
+}
+
+impl IoBuf for String {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl IoBuf for &'static str {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+impl IoBuf for Rc<[u8]> {
+    fn stable_ptr(&self) -> *const u8 {
         self.as_ptr()
     }
 
-    fn valid_len(&self) -> u32 {
-        self.len() as u32
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
     }
 }
 
-pub trait IoBufMut {
-    fn write_ptr(&mut self) -> *mut u8;
-    fn available_len(&self) -> u32;
-    unsafe fn set_valid_len(&mut self, size: u32);
+impl IoBuf for Arc<[u8]> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Lets the caller keep the memory alive past the op, e.g. when it is freed
+/// elsewhere.
+impl<T: IoBuf> IoBuf for ManuallyDrop<T> {
+    fn stable_ptr(&self) -> *const u8 {
+        T::stable_ptr(self)
+    }
+
+    fn bytes_init(&self) -> usize {
+        T::bytes_init(self)
+    }
+
+    fn bytes_total(&self) -> usize {
+        T::bytes_total(self)
+    }
+
+    fn buf_index(&self) -> Option<u16> {
+        T::buf_index(self)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl IoBuf for bytes::Bytes {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl IoBuf for bytes::BytesMut {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+/// Memory an op writes to. Reads append: they fill the bytes from `bytes_init` up
+/// to `bytes_total`, and then move `bytes_init` past what they wrote.
+pub trait IoBufMut: IoBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8;
+
+    /// Mark the bytes up to `pos` as initialized. Smaller positions than
+    /// `bytes_init` are ignored.
+    ///
+    /// # Safety
+    ///
+    /// The first `pos` bytes of the buffer must have been initialized.
+    unsafe fn set_init(&mut self, pos: usize);
 }
 
 impl IoBufMut for Vec<u8> {
-    fn write_ptr(&mut self) -> *mut u8 {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
         self.as_mut_ptr()
     }
 
-    fn available_len(&self) -> u32 {
-        self.capacity() as u32
+    unsafe fn set_init(&mut self, pos: usize) {
+        if pos > self.len() {
+            unsafe { self.set_len(pos) };
+        }
+    }
+}
+
+impl<T: IoBufMut> IoBufMut for ManuallyDrop<T> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        T::stable_mut_ptr(self)
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        unsafe { T::set_init(self, pos) }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl IoBufMut for bytes::BytesMut {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
     }
 
-    unsafe fn set_valid_len(&mut self, size: u32) {
-        unsafe {
-            self.set_len(size as usize);
+    unsafe fn set_init(&mut self, pos: usize) {
+        if pos > self.len() {
+            unsafe { self.set_len(pos) };
         }
     }
 }
@@ -0,0 +1,105 @@
+use super::{IoBuf, IoBufMut};
+
+/// Several buffers an op writes out in one go, e.g. a header and a body.
+pub trait IoVecBuf: 'static {
+    /// One iovec per buffer, covering its initialized bytes.
+    fn read_iovecs(&self) -> Vec<libc::iovec>;
+}
+
+/// Several buffers an op reads into, filled one after the other. Like with
+/// `IoBufMut`, reads append to what each buffer already holds.
+pub trait IoVecBufMut: IoVecBuf {
+    /// One iovec per buffer, covering its uninitialized bytes.
+    fn write_iovecs(&mut self) -> Vec<libc::iovec>;
+
+    /// Mark the first `size` bytes of the `write_iovecs` as initialized.
+    ///
+    /// # Safety
+    ///
+    /// Those bytes must have been initialized.
+    unsafe fn advance_init(&mut self, size: usize);
+}
+
+fn read_iovec<T: IoBuf>(buf: &T) -> libc::iovec {
+    libc::iovec {
+        iov_base: buf.stable_ptr() as *mut libc::c_void,
+        iov_len: buf.bytes_init(),
+    }
+}
+
+fn write_iovec<T: IoBufMut>(buf: &mut T) -> libc::iovec {
+    libc::iovec {
+        // Safety: `bytes_init` is within the buffer.
+        iov_base: unsafe { buf.stable_mut_ptr().add(buf.bytes_init()) } as *mut libc::c_void,
+        iov_len: buf.bytes_total() - buf.bytes_init(),
+    }
+}
+
+/// Mark the part of `size` that falls into `buf` as initialized, returning the rest.
+unsafe fn fill<T: IoBufMut>(buf: &mut T, size: usize) -> usize {
+    let init = buf.bytes_init();
+    let len = size.min(buf.bytes_total() - init);
+    unsafe { buf.set_init(init + len) };
+    size - len
+}
+
+impl<T: IoBuf> IoVecBuf for Vec<T> {
+    fn read_iovecs(&self) -> Vec<libc::iovec> {
+        self.iter().map(read_iovec).collect()
+    }
+}
+
+impl<T: IoBufMut> IoVecBufMut for Vec<T> {
+    fn write_iovecs(&mut self) -> Vec<libc::iovec> {
+        self.iter_mut().map(write_iovec).collect()
+    }
+
+    unsafe fn advance_init(&mut self, mut size: usize) {
+        for buf in self {
+            size = unsafe { fill(buf, size) };
+        }
+    }
+}
+
+impl<T: IoBuf, const N: usize> IoVecBuf for [T; N] {
+    fn read_iovecs(&self) -> Vec<libc::iovec> {
+        self.iter().map(read_iovec).collect()
+    }
+}
+
+impl<T: IoBufMut, const N: usize> IoVecBufMut for [T; N] {
+    fn write_iovecs(&mut self) -> Vec<libc::iovec> {
+        self.iter_mut().map(write_iovec).collect()
+    }
+
+    unsafe fn advance_init(&mut self, mut size: usize) {
+        for buf in self {
+            size = unsafe { fill(buf, size) };
+        }
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($T:ident $i:tt),+) => {
+        impl<$($T: IoBuf),+> IoVecBuf for ($($T,)+) {
+            fn read_iovecs(&self) -> Vec<libc::iovec> {
+                vec![$(read_iovec(&self.$i)),+]
+            }
+        }
+
+        impl<$($T: IoBufMut),+> IoVecBufMut for ($($T,)+) {
+            fn write_iovecs(&mut self) -> Vec<libc::iovec> {
+                vec![$(write_iovec(&mut self.$i)),+]
+            }
+
+            unsafe fn advance_init(&mut self, size: usize) {
+                $(let size = unsafe { fill(&mut self.$i, size) };)+
+                let _ = size;
+            }
+        }
+    };
+}
+
+impl_tuple!(A 0, B 1);
+impl_tuple!(A 0, B 1, C 2);
+impl_tuple!(A 0, B 1, C 2, D 3);
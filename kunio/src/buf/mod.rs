@@ -1,3 +1,19 @@
+mod aligned;
+mod fixed;
 mod io_buf;
+mod io_vec_buf;
+mod pool;
+mod ring;
+mod slice;
 
+pub use aligned::AlignedBuf;
+pub use fixed::{FixedBuf, FixedBufPool, FixedBufRegistry};
 pub use io_buf::{IoBuf, IoBufMut};
+pub use io_vec_buf::{IoVecBuf, IoVecBufMut};
+pub use pool::{BufPool, BufPoolConfig, BufPoolStats, PooledBuf};
+pub use ring::{BufRing, RingBuf};
+pub use slice::Slice;
+
+/// The result of an op together with the buffer it was given, which comes back
+/// whether or not the op succeeded.
+pub type BufResult<T, B> = (std::io::Result<T>, B);
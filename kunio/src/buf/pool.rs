@@ -0,0 +1,327 @@
+use std::cell::RefCell;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::buf::{IoBuf, IoBufMut};
+use crate::driver::RuntimeDriver;
+use crate::runtime::RUNTIME;
+
+/// How a runtime's `BufPool` is laid out, see `RuntimeBuilder::buf_pool`.
+#[derive(Clone, Debug)]
+pub struct BufPoolConfig {
+    size_classes: Vec<usize>,
+    max_free: usize,
+    fixed: usize,
+}
+
+impl BufPoolConfig {
+    pub fn new() -> Self {
+        Self {
+            size_classes: vec![512, 4096, 65536],
+            max_free: 64,
+            fixed: 0,
+        }
+    }
+
+    /// Capacities buffers are rounded up to. Larger requests are not pooled.
+    pub fn size_classes(mut self, size_classes: impl IntoIterator<Item = usize>) -> Self {
+        self.size_classes = size_classes.into_iter().collect();
+        self.size_classes.sort_unstable();
+        self.size_classes.dedup();
+        self
+    }
+
+    /// Free buffers kept per size class, the rest are freed when dropped.
+    pub fn max_free(mut self, max_free: usize) -> Self {
+        self.max_free = max_free;
+        self
+    }
+
+    /// Allocate `fixed` buffers per size class up front and register them with the
+    /// io_uring driver, so ops on them are issued as `ReadFixed`/`WriteFixed`.
+    ///
+    /// This takes the ring's buffer table, registering a `FixedBufPool` or
+    /// `FixedBufRegistry` on the same runtime then fails.
+    pub fn fixed(mut self, fixed: usize) -> Self {
+        self.fixed = fixed;
+        self
+    }
+
+    /// Fail with `InvalidInput` on a layout that cannot be registered.
+    pub(crate) fn check(&self) -> io::Result<()> {
+        if self.fixed == 0 {
+            return Ok(());
+        }
+        if self.size_classes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fixed buffers need at least one size class",
+            ));
+        }
+        if self.size_classes[0] == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "fixed buffers cannot have a size class of 0 bytes",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for BufPoolConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters of a `BufPool`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufPoolStats {
+    /// Buffers handed out from the free lists.
+    pub hits: u64,
+    /// Buffers that had to be allocated, including the ones too large to pool.
+    pub misses: u64,
+    /// Buffers currently handed out.
+    pub in_use: usize,
+    /// Buffers waiting in the free lists.
+    pub free: usize,
+}
+
+struct SizeClass {
+    capacity: usize,
+    /// Free buffers with the index they are registered at, if any.
+    free: Vec<(Vec<u8>, Option<u16>)>,
+}
+
+struct Pool {
+    classes: Vec<SizeClass>,
+    max_free: usize,
+    stats: BufPoolStats,
+}
+
+impl Pool {
+    fn get(&mut self, capacity: usize, shared: &Rc<RefCell<Pool>>) -> PooledBuf {
+        let class = self.classes.iter().position(|c| c.capacity >= capacity);
+        let (buf, index) = match class.and_then(|class| self.classes[class].free.pop()) {
+            Some(free) => {
+                self.stats.hits += 1;
+                self.stats.free -= 1;
+                free
+            }
+            None => {
+                self.stats.misses += 1;
+                let capacity = class.map_or(capacity, |class| self.classes[class].capacity);
+                (Vec::with_capacity(capacity), None)
+            }
+        };
+        self.stats.in_use += 1;
+        PooledBuf {
+            pool: shared.clone(),
+            buf: ManuallyDrop::new(buf),
+            class,
+            index,
+        }
+    }
+
+    fn put(&mut self, mut buf: Vec<u8>, class: Option<usize>, index: Option<u16>) {
+        self.stats.in_use -= 1;
+        let Some(class) = class else {
+            return;
+        };
+        let class = &mut self.classes[class];
+        // Registered buffers are never freed, the ring still points at them.
+        if index.is_some() || class.free.len() < self.max_free {
+            buf.clear();
+            class.free.push((buf, index));
+            self.stats.free += 1;
+        }
+    }
+}
+
+/// Reusable buffers in a few size classes. Each runtime has one, see
+/// `BufPool::current`.
+#[derive(Clone)]
+pub struct BufPool {
+    inner: Rc<RefCell<Pool>>,
+}
+
+impl BufPool {
+    /// A pool without registered buffers, whatever `config.fixed` says.
+    pub fn new(config: &BufPoolConfig) -> Self {
+        let classes = config
+            .size_classes
+            .iter()
+            .map(|&capacity| SizeClass {
+                capacity,
+                free: Vec::new(),
+            })
+            .collect();
+        Self {
+            inner: Rc::new(RefCell::new(Pool {
+                classes,
+                max_free: config.max_free,
+                stats: BufPoolStats::default(),
+            })),
+        }
+    }
+
+    pub(crate) fn with_driver(config: &BufPoolConfig, driver: &RuntimeDriver) -> io::Result<Self> {
+        let pool = Self::new(config);
+        let RuntimeDriver::Uring(driver) = driver else {
+            return Ok(pool);
+        };
+        if config.fixed == 0 {
+            return Ok(pool);
+        }
+        let count = config.fixed * config.size_classes.len();
+        if count > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many fixed buffers",
+            ));
+        }
+
+        let mut inner = pool.inner.borrow_mut();
+        let mut iovecs = Vec::with_capacity(count);
+        for class in &mut inner.classes {
+            for _ in 0..config.fixed {
+                let mut buf = Vec::with_capacity(class.capacity);
+                iovecs.push(libc::iovec {
+                    iov_base: buf.as_mut_ptr() as *mut _,
+                    iov_len: buf.capacity(),
+                });
+                class.free.push((buf, Some(iovecs.len() as u16 - 1)));
+            }
+        }
+        // Safety: registered buffers stay in the pool, or in a `PooledBuf` of it,
+        // until the pool and with it the runtime goes away.
+        unsafe { driver.register_buffers(&iovecs)? };
+        inner.stats.free = count;
+        drop(inner);
+        Ok(pool)
+    }
+
+    /// The current runtime's pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a runtime.
+    pub fn current() -> BufPool {
+        RUNTIME.with(|runtime| runtime.buf_pool.clone())
+    }
+
+    /// Take an empty buffer with at least `capacity` bytes.
+    pub fn get(&self, capacity: usize) -> PooledBuf {
+        self.inner.borrow_mut().get(capacity, &self.inner)
+    }
+
+    pub fn stats(&self) -> BufPoolStats {
+        self.inner.borrow().stats
+    }
+}
+
+/// A buffer of a `BufPool`, which goes back to the pool on drop.
+pub struct PooledBuf {
+    pool: Rc<RefCell<Pool>>,
+    buf: ManuallyDrop<Vec<u8>>,
+    class: Option<usize>,
+    index: Option<u16>,
+}
+
+impl PooledBuf {
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Append `src` to the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` does not fit in the remaining capacity, as growing would move
+    /// the data out of the pooled memory.
+    pub fn put_slice(&mut self, src: &[u8]) {
+        assert!(
+            src.len() <= self.buf.capacity() - self.buf.len(),
+            "pooled buffer overflow"
+        );
+        self.buf.extend_from_slice(src);
+    }
+}
+
+impl Deref for PooledBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        // Safety: `buf` is not used after this.
+        let buf = unsafe { ManuallyDrop::take(&mut self.buf) };
+        self.pool.borrow_mut().put(buf, self.class, self.index);
+    }
+}
+
+impl IoBuf for PooledBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.buf.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    fn buf_index(&self) -> Option<u16> {
+        self.index
+    }
+}
+
+impl IoBufMut for PooledBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.buf.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if pos > self.buf.len() {
+            unsafe { self.buf.set_len(pos) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::BufPoolConfig;
+    use crate::runtime::{DriverKind, Runtime};
+
+    #[test]
+    fn fixed_without_size_classes() {
+        let config = BufPoolConfig::new().size_classes([]).fixed(4);
+        let err = Runtime::builder()
+            .driver(DriverKind::Uring)
+            .buf_pool(config)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
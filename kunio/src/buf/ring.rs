@@ -0,0 +1,277 @@
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::task::Waker;
+
+use io_uring::types::BufRingEntry;
+
+use crate::buf::{IoBuf, IoBufMut};
+use crate::runtime::RUNTIME;
+
+const RING_ALIGN: usize = 4096;
+
+/// A provided buffer ring (`IORING_REGISTER_PBUF_RING`).
+///
+/// Receives issued on the ring let the kernel pick a buffer when data arrives,
+/// instead of every pending receive pinning its own buffer. Received data comes
+/// back as a `RingBuf`, which returns its slot to the ring on drop.
+#[derive(Clone)]
+pub struct BufRing {
+    inner: Rc<BufRingInner>,
+}
+
+struct BufRingInner {
+    bgid: u16,
+    entries: u16,
+    buf_len: usize,
+    ring: *mut BufRingEntry,
+    bufs: *mut u8,
+    tail: Cell<u16>,
+    /// The id of the runtime the ring is registered with.
+    registered: Cell<Option<u32>>,
+    /// How many buffers were handed back to the ring so far.
+    recycled: Cell<u64>,
+    /// Woken when a buffer is handed back, e.g. recvs the kernel ended with `ENOBUFS`.
+    waiters: RefCell<Vec<Waker>>,
+}
+
+impl BufRing {
+    /// Allocate `entries` buffers of `buf_len` bytes for buffer group `bgid`.
+    /// `entries` must be a power of two no larger than 32768.
+    pub fn new(bgid: u16, entries: u16, buf_len: usize) -> io::Result<BufRing> {
+        if !entries.is_power_of_two() || entries > 1 << 15 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer ring entries must be a power of two no larger than 32768",
+            ));
+        }
+        if buf_len == 0 || buf_len > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid buffer ring buffer length",
+            ));
+        }
+
+        let ring = unsafe { alloc::alloc_zeroed(ring_layout(entries)) } as *mut BufRingEntry;
+        if ring.is_null() {
+            alloc::handle_alloc_error(ring_layout(entries));
+        }
+        let bufs =
+            Box::into_raw(vec![0u8; entries as usize * buf_len].into_boxed_slice()) as *mut u8;
+
+        let inner = BufRingInner {
+            bgid,
+            entries,
+            buf_len,
+            ring,
+            bufs,
+            tail: Cell::new(0),
+            registered: Cell::new(None),
+            recycled: Cell::new(0),
+            waiters: RefCell::new(Vec::new()),
+        };
+        for bid in 0..entries {
+            inner.push(bid);
+        }
+        inner.publish();
+
+        Ok(BufRing {
+            inner: Rc::new(inner),
+        })
+    }
+
+    /// Register the ring with the current runtime's driver.
+    pub fn register(&self) -> io::Result<()> {
+        let inner = &self.inner;
+        // Safety: the ring memory is only freed once the kernel has let go of it, see
+        // the drop of `BufRingInner`.
+        RUNTIME.with(|runtime| -> io::Result<()> {
+            unsafe {
+                runtime.driver.uring()?.register_buf_ring(
+                    inner.ring as u64,
+                    inner.entries,
+                    inner.bgid,
+                )?
+            };
+            inner.registered.set(Some(runtime.id));
+            Ok(())
+        })
+    }
+
+    pub fn unregister(&self) -> io::Result<()> {
+        self.inner.unregister()
+    }
+
+    pub fn bgid(&self) -> u16 {
+        self.inner.bgid
+    }
+
+    pub fn buf_len(&self) -> usize {
+        self.inner.buf_len
+    }
+
+    /// How many buffers were handed back to the ring so far. If it did not change,
+    /// a ring that ran dry is still dry.
+    pub(crate) fn recycled(&self) -> u64 {
+        self.inner.recycled.get()
+    }
+
+    /// Wake `waker` once a buffer is handed back to the ring.
+    pub(crate) fn wait_recycle(&self, waker: &Waker) {
+        let mut waiters = self.inner.waiters.borrow_mut();
+        if !waiters.iter().any(|w| w.will_wake(waker)) {
+            waiters.push(waker.clone());
+        }
+    }
+
+    /// Take ownership of the buffer `bid` the kernel filled with `len` bytes.
+    ///
+    /// # Safety
+    ///
+    /// `bid` must come from a completion of this ring and must not be owned yet.
+    pub(crate) unsafe fn get_buf(&self, bid: u16, len: usize) -> RingBuf {
+        RingBuf {
+            ring: self.inner.clone(),
+            bid,
+            len,
+        }
+    }
+}
+
+impl BufRingInner {
+    fn slot(&self, bid: u16) -> *mut u8 {
+        unsafe { self.bufs.add(bid as usize * self.buf_len) }
+    }
+
+    /// Write the descriptor of `bid` at the local tail.
+    fn push(&self, bid: u16) {
+        let tail = self.tail.get();
+        let index = (tail & (self.entries - 1)) as usize;
+        // Safety: `index` is in bounds; the kernel does not read past the published tail.
+        let entry = unsafe { &mut *self.ring.add(index) };
+        entry.set_addr(self.slot(bid) as u64);
+        entry.set_len(self.buf_len as u32);
+        entry.set_bid(bid);
+        self.tail.set(tail.wrapping_add(1));
+    }
+
+    /// Make the pushed descriptors visible to the kernel.
+    fn publish(&self) {
+        // Safety: the tail overlays the reserved field of the first entry.
+        unsafe {
+            let tail = BufRingEntry::tail(self.ring) as *const AtomicU16;
+            (*tail).store(self.tail.get(), Ordering::Release);
+        }
+    }
+
+    fn recycle(&self, bid: u16) {
+        self.push(bid);
+        self.publish();
+        self.recycled.set(self.recycled.get() + 1);
+        let waiters = std::mem::take(&mut *self.waiters.borrow_mut());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+
+    fn unregister(&self) -> io::Result<()> {
+        let Some(id) = self.registered.get() else {
+            return Ok(());
+        };
+        if !RUNTIME.is_set() || RUNTIME.with(|runtime| runtime.id) != id {
+            return Err(io::Error::other(
+                "buffer ring is registered with another runtime",
+            ));
+        }
+        RUNTIME.with(|runtime| runtime.driver.uring()?.unregister_buf_ring(self.bgid))?;
+        self.registered.set(None);
+        Ok(())
+    }
+}
+
+impl Drop for BufRingInner {
+    fn drop(&mut self) {
+        // The kernel may still fill buffers of a ring it knows about, so if it
+        // cannot be unregistered the memory is leaked rather than freed under it.
+        if self.unregister().is_err() {
+            return;
+        }
+        // Safety: allocated in `BufRing::new` with the same sizes.
+        unsafe {
+            alloc::dealloc(self.ring as *mut u8, ring_layout(self.entries));
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                self.bufs,
+                self.entries as usize * self.buf_len,
+            )));
+        }
+    }
+}
+
+fn ring_layout(entries: u16) -> Layout {
+    Layout::from_size_align(entries as usize * size_of::<BufRingEntry>(), RING_ALIGN).unwrap()
+}
+
+/// A buffer picked by the kernel from a `BufRing`. Dropping it hands the slot
+/// back to the ring.
+pub struct RingBuf {
+    ring: Rc<BufRingInner>,
+    bid: u16,
+    len: usize,
+}
+
+impl RingBuf {
+    pub fn bid(&self) -> u16 {
+        self.bid
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.buf_len
+    }
+}
+
+impl Deref for RingBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ring.slot(self.bid), self.len) }
+    }
+}
+
+impl DerefMut for RingBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ring.slot(self.bid), self.len) }
+    }
+}
+
+impl Drop for RingBuf {
+    fn drop(&mut self) {
+        self.ring.recycle(self.bid);
+    }
+}
+
+impl IoBuf for RingBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.ring.slot(self.bid)
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.ring.buf_len
+    }
+}
+
+impl IoBufMut for RingBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.ring.slot(self.bid)
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.len = self.len.max(pos);
+    }
+}
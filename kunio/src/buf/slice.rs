@@ -0,0 +1,179 @@
+use super::{IoBuf, IoBufMut};
+
+/// The bytes `begin..end` of a buffer, which ops treat as a buffer of its own.
+/// Made by `IoBuf::slice`, `into_inner` gives the buffer back.
+pub struct Slice<T> {
+    buf: T,
+    begin: usize,
+    end: usize,
+}
+
+impl<T> Slice<T> {
+    pub(crate) fn new(buf: T, begin: usize, end: usize) -> Self {
+        Self { buf, begin, end }
+    }
+
+    pub fn begin(&self) -> usize {
+        self.begin
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.buf
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.buf
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+}
+
+impl<T: IoBuf> Slice<T> {
+    /// `begin` and `end`, cut down to what is left of the buffer if it was shrunk
+    /// through `get_mut` since it was sliced.
+    fn bounds(&self) -> (usize, usize) {
+        let total = self.buf.bytes_total();
+        (self.begin.min(total), self.end.min(total))
+    }
+}
+
+impl<T: IoBuf> IoBuf for Slice<T> {
+    fn stable_ptr(&self) -> *const u8 {
+        let (begin, _) = self.bounds();
+        unsafe { self.buf.stable_ptr().add(begin) }
+    }
+
+    fn bytes_init(&self) -> usize {
+        let (begin, end) = self.bounds();
+        self.buf.bytes_init().clamp(begin, end) - begin
+    }
+
+    fn bytes_total(&self) -> usize {
+        let (begin, end) = self.bounds();
+        end - begin
+    }
+
+    fn buf_index(&self) -> Option<u16> {
+        self.buf.buf_index()
+    }
+}
+
+impl<T: IoBufMut> IoBufMut for Slice<T> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        let (begin, _) = self.bounds();
+        unsafe { self.buf.stable_mut_ptr().add(begin) }
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        // The buffer may have been cut short through `get_mut` since it was sliced.
+        if self.buf.bytes_init() >= self.begin {
+            unsafe { self.buf.set_init(self.begin + pos) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    use crate::buf::{IoBuf, IoBufMut};
+    use crate::driver::op::Op;
+    use crate::runtime::Runtime;
+
+    #[test]
+    fn bounds() {
+        let mut buf = Vec::with_capacity(16);
+        buf.extend_from_slice(b"hello");
+        let total = buf.capacity();
+
+        let slice = buf.slice(..);
+        assert_eq!((slice.begin(), slice.end()), (0, total));
+        assert_eq!(slice.bytes_init(), 5);
+        let slice = slice.into_inner().slice(1..=3);
+        assert_eq!((slice.bytes_init(), slice.bytes_total()), (3, 3));
+        let slice = slice.into_inner().slice(2..);
+        assert_eq!((slice.bytes_init(), slice.bytes_total()), (3, total - 2));
+        let slice = slice.into_inner().slice(5..);
+        assert_eq!(slice.bytes_init(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn past_total() {
+        let buf = Vec::<u8>::with_capacity(16);
+        let total = buf.capacity();
+        buf.slice(..total + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn reversed() {
+        let (begin, end) = (3, 2);
+        b"hello".to_vec().slice(begin..end);
+    }
+
+    #[test]
+    #[should_panic(expected = "initialized bytes")]
+    fn past_init() {
+        let mut buf = Vec::with_capacity(16);
+        buf.extend_from_slice(b"hello");
+        buf.slice(6..);
+    }
+
+    #[test]
+    fn set_init() {
+        let mut buf = Vec::with_capacity(16);
+        buf.extend_from_slice(b"hello");
+        let mut slice = buf.slice(5..);
+        unsafe {
+            slice.stable_mut_ptr().copy_from(b" world".as_ptr(), 6);
+            slice.set_init(6);
+        }
+        assert_eq!(slice.into_inner(), b"hello world");
+
+        // A buffer cut short after slicing is not extended over the gap.
+        let mut slice = b"hello".to_vec().slice(5..);
+        slice.get_mut().clear();
+        unsafe { slice.set_init(3) };
+        assert!(slice.into_inner().is_empty());
+    }
+
+    #[test]
+    fn read_after_shrink() {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(b"hello");
+        let mut slice = buf.slice(2..);
+        slice.get_mut().shrink_to_fit();
+        let total = slice.get_ref().capacity();
+        assert_eq!(slice.bytes_total(), total - 2);
+
+        // The read is bounded by what is left of the buffer, not by the bytes it
+        // had when it was sliced.
+        let (ours, mut peer) = UnixStream::pair().unwrap();
+        peer.write_all(&[b'x'; 64]).unwrap();
+        let runtime = Runtime::builder().build().unwrap();
+        let (result, slice) = runtime.block_on(async move {
+            let mut completion = Op::recv(ours.as_raw_fd(), slice).await;
+            let result = completion.result;
+            if let Ok(n) = result {
+                let init = completion.data.buf.bytes_init();
+                unsafe { completion.data.buf.set_init(init + n as usize) };
+            }
+            drop(ours);
+            (result, completion.data.buf)
+        });
+        let read = result.unwrap() as usize;
+        assert_eq!(read, total - 5);
+        let buf = slice.into_inner();
+        assert_eq!(buf.len(), 5 + read);
+        assert_eq!(&buf[..5], b"hello");
+    }
+}
@@ -0,0 +1,79 @@
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::{Arc, Mutex};
+
+use threadpool::ThreadPool;
+
+use super::sqe::Sqe;
+
+/// Runs SQEs with blocking syscalls on a thread pool. Finished results are
+/// collected until taken, and an eventfd is signalled so the driver can wait on
+/// them with epoll or io_uring.
+pub(crate) struct BlockingPool {
+    pool: ThreadPool,
+    event: Arc<OwnedFd>,
+    completed: Arc<Mutex<Vec<(u64, i32)>>>,
+}
+
+impl BlockingPool {
+    pub fn new(threads: usize) -> io::Result<Self> {
+        let event = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if event < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            pool: ThreadPool::new(threads.max(1)),
+            event: Arc::new(unsafe { OwnedFd::from_raw_fd(event) }),
+            completed: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Run SQEs in order. A broken link cancels the rest.
+    pub fn spawn(&self, job: Vec<(u64, Sqe)>) {
+        let completed = self.completed.clone();
+        let event = self.event.clone();
+        self.pool.execute(move || {
+            let mut broken = false;
+            let results: Vec<_> = job
+                .into_iter()
+                .map(|(id, sqe)| {
+                    let result = if broken {
+                        -libc::ECANCELED
+                    } else {
+                        sqe.perform()
+                    };
+                    broken = broken || sqe.breaks_link(result);
+                    (id, result)
+                })
+                .collect();
+            completed.lock().unwrap().extend(results);
+            let one = 1u64;
+            unsafe {
+                libc::write(
+                    event.as_raw_fd(),
+                    &one as *const u64 as *const libc::c_void,
+                    size_of::<u64>(),
+                )
+            };
+        });
+    }
+
+    /// Reset the eventfd and take the results finished so far.
+    pub fn take_completed(&self) -> Vec<(u64, i32)> {
+        let mut count = 0u64;
+        unsafe {
+            libc::read(
+                self.event.as_raw_fd(),
+                &mut count as *mut u64 as *mut libc::c_void,
+                size_of::<u64>(),
+            )
+        };
+        std::mem::take(&mut *self.completed.lock().unwrap())
+    }
+}
+
+impl AsRawFd for BlockingPool {
+    fn as_raw_fd(&self) -> RawFd {
+        self.event.as_raw_fd()
+    }
+}
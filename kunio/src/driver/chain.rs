@@ -0,0 +1,106 @@
+use std::io;
+
+use crate::runtime::RUNTIME;
+
+/// Submit several ops as one linked chain (`IOSQE_IO_LINK`).
+///
+/// Every op built inside `submit` is queued instead of being submitted right away,
+/// and the whole chain is pushed to the SQ at once. An op of a chain only starts
+/// after the previous one completed; if one fails (for reads and writes, a short
+/// count counts as failure) the rest complete with `ECANCELED`. With `hard` links
+/// the chain is not broken by failures.
+///
+/// ```ignore
+/// let (write, read) = OpChain::new().submit(|| {
+///     Ok((Op::write_at(fd, buf, 0), Op::read_at(fd, Vec::with_capacity(64), 0)))
+/// })?;
+/// let (write, read) = (write.await, read.await);
+/// ```
+pub struct OpChain {
+    hard: bool,
+}
+
+impl OpChain {
+    pub fn new() -> Self {
+        Self { hard: false }
+    }
+
+    /// Use `IOSQE_IO_HARDLINK`, so that a failed op does not cancel the rest.
+    pub fn hard(mut self) -> Self {
+        self.hard = true;
+        self
+    }
+
+    /// Run `build`, linking every op it submits. Nothing is submitted if `build`
+    /// fails.
+    pub fn submit<R>(self, build: impl FnOnce() -> io::Result<R>) -> io::Result<R> {
+        RUNTIME.with(|runtime| runtime.driver.begin_chain())?;
+        let res = build();
+        let commit = RUNTIME.with(|runtime| runtime.driver.end_chain(self.hard, res.is_ok()));
+        match (res, commit) {
+            (Ok(ops), Ok(())) => Ok(ops),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        }
+    }
+}
+
+impl Default for OpChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsRawFd;
+
+    use io_uring::opcode;
+
+    use super::OpChain;
+    use crate::driver::op::Op;
+    use crate::driver::{Fault, FaultRule};
+    use crate::fs::File;
+    use crate::runtime::{DriverKind, Runtime};
+
+    /// Write and then read back "hello" as a chain, with the write failing.
+    fn failed_write(chain: OpChain) -> (i32, std::io::Result<i32>) {
+        let runtime = Runtime::builder()
+            .driver(DriverKind::Mock { seed: 3 })
+            .build()
+            .unwrap();
+        let mock = runtime.driver.mock().unwrap();
+        mock.add_file("chain.txt", "hello");
+        mock.inject(FaultRule::new(Fault::Error(libc::EIO)).opcode(opcode::Write::CODE));
+        runtime.block_on(async {
+            let file = File::open("chain.txt").await.unwrap();
+            let fd = file.as_raw_fd();
+            let (write, read) = chain
+                .submit(|| {
+                    Ok((
+                        Op::write_at(fd, b"world".to_vec(), 0),
+                        Op::read_at(fd, Vec::with_capacity(16), 0),
+                    ))
+                })
+                .unwrap();
+            let (write, read) = (write.await, read.await);
+            (
+                write.result.unwrap_err().raw_os_error().unwrap(),
+                read.result,
+            )
+        })
+    }
+
+    #[test]
+    fn failed_link_cancels_the_rest() {
+        let (write, read) = failed_write(OpChain::new());
+        assert_eq!(write, libc::EIO);
+        assert_eq!(read.unwrap_err().raw_os_error(), Some(libc::ECANCELED));
+    }
+
+    #[test]
+    fn hard_link_runs_the_rest() {
+        let (write, read) = failed_write(OpChain::new().hard());
+        assert_eq!(write, libc::EIO);
+        assert_eq!(read.unwrap(), 5);
+    }
+}
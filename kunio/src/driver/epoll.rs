@@ -0,0 +1,518 @@
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::task::{Context, Poll};
+
+use io_uring::{opcode, squeue};
+
+use super::Driver;
+use super::blocking::BlockingPool;
+use super::op::{Cqe, Op, OpStage, UringOp};
+use super::sqe::{Sqe, syscall_result};
+use crate::utils::IdGenerator;
+
+const DEFAULT_BLOCKING_THREADS: usize = 4;
+const MAX_EVENTS: usize = 64;
+// epoll token of the eventfd the blocking pool signals
+const EVENTFD_TOKEN: u64 = u64::MAX;
+
+/// A readiness based driver for kernels without io_uring.
+///
+/// Ops are still described by the SQE they build, which this driver interprets:
+/// socket ops are retried on epoll readiness, everything else (file I/O, open,
+/// close, linked chains) runs on a small blocking pool owned by the driver.
+/// Fixed files, provided buffers and multishot ops are not supported.
+pub struct EpollDriver {
+    inner: UnsafeCell<EpollInner>,
+}
+
+impl EpollDriver {
+    pub fn new() -> io::Result<Self> {
+        Self::with_blocking_threads(DEFAULT_BLOCKING_THREADS)
+    }
+
+    pub fn with_blocking_threads(threads: usize) -> io::Result<Self> {
+        let inner = EpollInner::new(threads)?;
+        Ok(Self {
+            inner: UnsafeCell::new(inner),
+        })
+    }
+
+    pub(crate) fn begin_chain(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).begin_chain() }
+    }
+
+    pub(crate) fn end_chain(&self, hard: bool, commit: bool) -> io::Result<()> {
+        unsafe { (*self.inner.get()).end_chain(hard, commit) }
+    }
+}
+
+impl Driver for EpollDriver {
+    fn submit_op<T: UringOp>(&self, data: T) -> Op<T> {
+        unsafe { (*self.inner.get()).submit_op(data) }
+    }
+
+    fn poll_op<T: UringOp>(&self, op: &mut Op<T>, cx: &mut Context<'_>) -> Poll<Cqe> {
+        unsafe { super::poll_op_stage(&mut (*self.inner.get()).ops, op.id, cx) }
+    }
+
+    fn cancel_op(&self, id: u64) -> io::Result<()> {
+        unsafe { (*self.inner.get()).cancel(id) };
+        Ok(())
+    }
+
+    fn detach_op(&self, id: u64, data: Box<dyn UringOp>) {
+        let inner = unsafe { &mut *self.inner.get() };
+        if super::detach_op_stage(&mut inner.ops, id, data) {
+            inner.cancel(id);
+        }
+    }
+
+    fn park(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).park() }
+    }
+
+    fn supports(&self, opcode: u8) -> bool {
+        Sqe::performable(opcode) || opcode == opcode::AsyncCancel::CODE
+    }
+}
+
+impl AsRawFd for EpollDriver {
+    fn as_raw_fd(&self) -> RawFd {
+        unsafe { (*self.inner.get()).epoll.as_raw_fd() }
+    }
+}
+
+impl Sqe {
+    fn is_readiness(&self) -> bool {
+        matches!(
+            self.opcode,
+            opcode::Recv::CODE
+                | opcode::Send::CODE
+                | opcode::RecvMsg::CODE
+                | opcode::SendMsg::CODE
+                | opcode::Accept::CODE
+                | opcode::Connect::CODE
+        )
+    }
+}
+
+/// A socket op waiting for its fd to become ready.
+struct Pending {
+    id: u64,
+    sqe: Sqe,
+    // a connect that returned EINPROGRESS, whose result is read from SO_ERROR
+    connecting: bool,
+}
+
+impl Pending {
+    /// Attempt the op without blocking, `None` if the fd is not ready yet.
+    fn try_perform(&mut self) -> Option<i32> {
+        let sqe = self.sqe;
+        loop {
+            let result = match sqe.opcode {
+                opcode::Recv::CODE => unsafe {
+                    syscall_result(libc::recv(
+                        sqe.fd,
+                        sqe.addr as *mut libc::c_void,
+                        sqe.len as usize,
+                        sqe.op_flags as i32 | libc::MSG_DONTWAIT,
+                    ) as i64)
+                },
+                opcode::Send::CODE => unsafe {
+                    syscall_result(libc::send(
+                        sqe.fd,
+                        sqe.addr as *const libc::c_void,
+                        sqe.len as usize,
+                        sqe.op_flags as i32 | libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+                    ) as i64)
+                },
+                opcode::Connect::CODE if self.connecting => {
+                    let mut err: libc::c_int = 0;
+                    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+                    let ret = unsafe {
+                        libc::getsockopt(
+                            sqe.fd,
+                            libc::SOL_SOCKET,
+                            libc::SO_ERROR,
+                            &mut err as *mut _ as *mut libc::c_void,
+                            &mut len,
+                        )
+                    };
+                    match syscall_result(ret as i64) {
+                        0 if err == 0 => {
+                            // Not connected yet if the event was for another op.
+                            if !is_connected(sqe.fd) {
+                                return None;
+                            }
+                            0
+                        }
+                        0 => -err,
+                        e => e,
+                    }
+                }
+                _ => {
+                    set_nonblocking(sqe.fd);
+                    sqe.perform()
+                }
+            };
+            match -result {
+                libc::EINTR => continue,
+                libc::EAGAIN => return None,
+                libc::EINPROGRESS if sqe.opcode == opcode::Connect::CODE => {
+                    self.connecting = true;
+                    return None;
+                }
+                _ => return Some(result),
+            }
+        }
+    }
+}
+
+fn set_nonblocking(fd: RawFd) {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags >= 0 && flags & libc::O_NONBLOCK == 0 {
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+    }
+}
+
+fn is_connected(fd: RawFd) -> bool {
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    unsafe { libc::getpeername(fd, &mut addr as *mut _ as *mut libc::sockaddr, &mut len) == 0 }
+}
+
+struct EpollInner {
+    ops: HashMap<u64, OpStage>,
+    id_generator: IdGenerator,
+    epoll: OwnedFd,
+    pending: HashMap<RawFd, Vec<Pending>>,
+    blocking: BlockingPool,
+    waiting: usize,
+    // SQEs of an `OpChain` being built, run as one job when it is submitted
+    chain: Option<Vec<(u64, Sqe)>>,
+}
+
+impl EpollInner {
+    fn new(threads: usize) -> io::Result<Self> {
+        let epoll = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll) };
+        let blocking = BlockingPool::new(threads)?;
+
+        let mut ev = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: EVENTFD_TOKEN,
+        };
+        if unsafe {
+            libc::epoll_ctl(
+                epoll.as_raw_fd(),
+                libc::EPOLL_CTL_ADD,
+                blocking.as_raw_fd(),
+                &mut ev,
+            )
+        } < 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ops: HashMap::new(),
+            id_generator: IdGenerator::new(),
+            epoll,
+            pending: HashMap::new(),
+            blocking,
+            waiting: 0,
+            chain: None,
+        })
+    }
+
+    fn submit_op<T: UringOp>(&mut self, data: T) -> Op<T> {
+        let id = self.id_generator.gen_id();
+        let mut op = Op::new(id, data);
+
+        let sqe = Sqe::new(op.build_sqe());
+        self.ops.insert(id, OpStage::Submitted);
+        match self.chain {
+            Some(ref mut chain) => chain.push((id, sqe)),
+            None => self.dispatch(id, sqe),
+        }
+        op
+    }
+
+    fn dispatch(&mut self, id: u64, sqe: Sqe) {
+        if sqe.unsupported() {
+            self.complete(id, -libc::EOPNOTSUPP);
+        } else if sqe.opcode == opcode::AsyncCancel::CODE {
+            let result = self.cancel(sqe.addr);
+            self.complete(id, result);
+        } else if sqe.is_readiness() {
+            let mut pending = Pending {
+                id,
+                sqe,
+                connecting: false,
+            };
+            match pending.try_perform() {
+                Some(result) => self.complete(id, result),
+                None => match self.watch(sqe.fd) {
+                    Ok(()) => {
+                        self.waiting += 1;
+                        self.pending.entry(sqe.fd).or_default().push(pending);
+                    }
+                    Err(e) => self.complete(id, -e.raw_os_error().unwrap_or(libc::EIO)),
+                },
+            }
+        } else {
+            self.spawn_job(vec![(id, sqe)]);
+        }
+    }
+
+    /// (Re-)arm edge triggered readiness of `fd`. Modifying an existing registration
+    /// reports readiness that is already there, so no edge is lost in between.
+    fn watch(&mut self, fd: RawFd) -> io::Result<()> {
+        let mut ev = libc::epoll_event {
+            events: (libc::EPOLLIN | libc::EPOLLOUT | libc::EPOLLRDHUP | libc::EPOLLET) as u32,
+            u64: fd as u64,
+        };
+        let epoll = self.epoll.as_raw_fd();
+        if unsafe { libc::epoll_ctl(epoll, libc::EPOLL_CTL_MOD, fd, &mut ev) } == 0 {
+            return Ok(());
+        }
+        if unsafe { libc::epoll_ctl(epoll, libc::EPOLL_CTL_ADD, fd, &mut ev) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Remove `fd` from the epoll set, once no op waits for it anymore. It may be
+    /// closed already, which removed it too.
+    fn unwatch(&mut self, fd: RawFd) {
+        unsafe {
+            libc::epoll_ctl(
+                self.epoll.as_raw_fd(),
+                libc::EPOLL_CTL_DEL,
+                fd,
+                std::ptr::null_mut(),
+            )
+        };
+    }
+
+    /// Run SQEs on the blocking pool, in order. A broken link cancels the rest.
+    fn spawn_job(&mut self, job: Vec<(u64, Sqe)>) {
+        for (_, sqe) in &job {
+            if sqe.opcode == opcode::Close::CODE {
+                self.forget(sqe.fd);
+            }
+        }
+        self.waiting += job.len();
+        self.blocking.spawn(job);
+    }
+
+    /// Stop watching `fd` as it is about to be closed. Ops still waiting on it would
+    /// never hear from epoll again, so they are canceled.
+    fn forget(&mut self, fd: RawFd) {
+        let Some(ops) = self.pending.remove(&fd) else {
+            return;
+        };
+        self.unwatch(fd);
+        for pending in ops {
+            self.waiting -= 1;
+            self.complete(pending.id, -libc::ECANCELED);
+        }
+    }
+
+    /// Cancel a socket op still waiting for readiness.
+    fn cancel(&mut self, target: u64) -> i32 {
+        let found = self.pending.iter_mut().find_map(|(&fd, ops)| {
+            let pos = ops.iter().position(|pending| pending.id == target)?;
+            ops.remove(pos);
+            Some((fd, ops.is_empty()))
+        });
+        let Some((fd, idle)) = found else {
+            return -libc::ENOENT;
+        };
+        if idle {
+            self.pending.remove(&fd);
+            self.unwatch(fd);
+        }
+        self.waiting -= 1;
+        self.complete(target, -libc::ECANCELED);
+        0
+    }
+
+    fn complete(&mut self, id: u64, result: i32) {
+        super::complete_op(&mut self.ops, id, Cqe::new(result, 0));
+    }
+
+    fn begin_chain(&mut self) -> io::Result<()> {
+        if self.chain.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "op chains cannot be nested",
+            ));
+        }
+        self.chain = Some(Vec::new());
+        Ok(())
+    }
+
+    fn end_chain(&mut self, hard: bool, commit: bool) -> io::Result<()> {
+        let chain = self.chain.take().unwrap_or_default();
+        if !commit {
+            for (id, _) in chain {
+                self.ops.remove(&id);
+            }
+            return Ok(());
+        }
+
+        let flag = if hard {
+            squeue::Flags::IO_HARDLINK
+        } else {
+            squeue::Flags::IO_LINK
+        };
+        let len = chain.len();
+        let job = chain
+            .into_iter()
+            .enumerate()
+            .map(|(i, (id, mut sqe))| {
+                if i + 1 < len {
+                    sqe.flags |= flag.bits();
+                }
+                (id, sqe)
+            })
+            .collect();
+        self.spawn_job(job);
+        Ok(())
+    }
+
+    fn park(&mut self) -> io::Result<()> {
+        if self.waiting == 0 {
+            return Ok(());
+        }
+
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; MAX_EVENTS];
+        let n = unsafe {
+            libc::epoll_wait(
+                self.epoll.as_raw_fd(),
+                events.as_mut_ptr(),
+                MAX_EVENTS as i32,
+                -1,
+            )
+        };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(());
+            }
+            return Err(err);
+        }
+
+        for ev in &events[..n as usize] {
+            if ev.u64 == EVENTFD_TOKEN {
+                self.complete_jobs();
+            } else {
+                self.retry(ev.u64 as RawFd);
+            }
+        }
+        Ok(())
+    }
+
+    fn complete_jobs(&mut self) {
+        let completed = self.blocking.take_completed();
+        for (id, result) in completed {
+            self.waiting -= 1;
+            self.complete(id, result);
+        }
+    }
+
+    fn retry(&mut self, fd: RawFd) {
+        let Some(ops) = self.pending.remove(&fd) else {
+            return;
+        };
+        let mut still_pending = Vec::new();
+        for mut pending in ops {
+            match pending.try_perform() {
+                Some(result) => {
+                    self.waiting -= 1;
+                    self.complete(pending.id, result);
+                }
+                None => still_pending.push(pending),
+            }
+        }
+        if still_pending.is_empty() {
+            self.unwatch(fd);
+        } else {
+            self.pending.insert(fd, still_pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::fd::{AsRawFd, IntoRawFd, RawFd};
+    use std::os::unix::net::UnixStream;
+    use std::pin::pin;
+    use std::task::Poll;
+
+    use crate::driver::RuntimeDriver;
+    use crate::driver::op::Op;
+    use crate::runtime::{DriverKind, Runtime};
+
+    fn runtime() -> Runtime {
+        Runtime::builder()
+            .driver(DriverKind::Epoll)
+            .build()
+            .unwrap()
+    }
+
+    /// Whether `fd` is in the driver's epoll set, as listed in its fdinfo.
+    fn watched(runtime: &Runtime, fd: RawFd) -> bool {
+        let RuntimeDriver::Epoll(driver) = &runtime.driver else {
+            unreachable!()
+        };
+        let info = std::fs::read_to_string(format!("/proc/self/fdinfo/{}", driver.as_raw_fd()));
+        let tfd = format!("tfd: {fd:8} ");
+        info.unwrap().lines().any(|line| line.starts_with(&tfd))
+    }
+
+    #[test]
+    fn unwatch_after_last_op() {
+        let (ours, mut peer) = UnixStream::pair().unwrap();
+        let runtime = runtime();
+        runtime.block_on(async {
+            let mut op = pin!(Op::recv(ours.as_raw_fd(), Vec::with_capacity(16)));
+            std::future::poll_fn(|cx| {
+                assert!(op.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+            assert!(watched(&runtime, ours.as_raw_fd()));
+            peer.write_all(b"hello").unwrap();
+            assert_eq!(op.await.result.unwrap(), 5);
+        });
+        assert!(!watched(&runtime, ours.as_raw_fd()));
+    }
+
+    #[test]
+    fn close_cancels_waiting_ops() {
+        let (ours, _peer) = UnixStream::pair().unwrap();
+        let fd = ours.into_raw_fd();
+        let runtime = runtime();
+        runtime.block_on(async {
+            let mut op = pin!(Op::recv(fd, Vec::with_capacity(16)));
+            std::future::poll_fn(|cx| {
+                assert!(op.as_mut().poll(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+            Op::close(fd).await.result.unwrap();
+            let err = op.await.result.unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(libc::ECANCELED));
+        });
+    }
+}
@@ -0,0 +1,110 @@
+use std::os::fd::RawFd;
+use std::time::Duration;
+
+use super::sqe::Sqe;
+
+/// What to do to an op matched by a `FaultRule`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Complete the op with `-errno` in place of the result it got.
+    Error(i32),
+    /// Let a read or write, vectored or not, move at most this many bytes. Other
+    /// ops are not matched.
+    Short(u32),
+    /// Hold the completion back for this long.
+    Delay(Duration),
+}
+
+/// Injects a `Fault` into the ops matching an opcode (`opcode::*::CODE`) and/or an
+/// fd. For fixed file ops the fd is the slot in the registered file table.
+#[derive(Clone, Debug)]
+pub struct FaultRule {
+    opcode: Option<u8>,
+    fd: Option<RawFd>,
+    fault: Fault,
+    times: Option<usize>,
+}
+
+impl FaultRule {
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            opcode: None,
+            fd: None,
+            fault,
+            times: None,
+        }
+    }
+
+    pub fn opcode(mut self, opcode: u8) -> Self {
+        self.opcode = Some(opcode);
+        self
+    }
+
+    pub fn fd(mut self, fd: RawFd) -> Self {
+        self.fd = Some(fd);
+        self
+    }
+
+    /// Only fire for the next `times` matching ops, instead of all of them.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, sqe: &Sqe) -> bool {
+        self.times != Some(0)
+            && self.opcode.is_none_or(|opcode| opcode == sqe.opcode)
+            && self.fd.is_none_or(|fd| fd == sqe.fd)
+            && (!matches!(self.fault, Fault::Short(_)) || sqe.shortenable())
+    }
+}
+
+/// The faults injected into one op.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Injected {
+    pub error: Option<i32>,
+    pub short: Option<u32>,
+    pub delay: Duration,
+}
+
+#[derive(Default)]
+pub(crate) struct Faults {
+    rules: Vec<FaultRule>,
+}
+
+impl Faults {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn push(&mut self, rule: FaultRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Match `sqe` against the rules, in the order they were added. The first
+    /// matching error or short rule fires, delays of all matching rules add up. A
+    /// rule that fires uses up one of its `times`.
+    pub fn inject(&mut self, sqe: &Sqe) -> Injected {
+        let mut injected = Injected::default();
+        for rule in self.rules.iter_mut().filter(|rule| rule.matches(sqe)) {
+            match rule.fault {
+                Fault::Error(_) | Fault::Short(_)
+                    if injected.error.is_some() || injected.short.is_some() =>
+                {
+                    continue;
+                }
+                Fault::Error(errno) => injected.error = Some(errno),
+                Fault::Short(len) => injected.short = Some(len),
+                Fault::Delay(delay) => injected.delay += delay,
+            }
+            if let Some(times) = &mut rule.times {
+                *times -= 1;
+            }
+        }
+        injected
+    }
+}
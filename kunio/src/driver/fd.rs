@@ -0,0 +1,135 @@
+use std::io;
+use std::os::fd::RawFd;
+
+use crate::driver::mock::is_mock_fd;
+use crate::driver::op::Op;
+use crate::runtime::{RUNTIME, spawn};
+
+/// A slot in the registered file table of the current runtime's `UringDriver`.
+///
+/// Ops issued on a `FixedFd` use `types::Fixed`, which saves the kernel the
+/// fdget/fdput pair of a raw descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedFd(u32);
+
+impl FixedFd {
+    pub fn new(slot: u32) -> Self {
+        Self(slot)
+    }
+
+    pub fn slot(&self) -> u32 {
+        self.0
+    }
+
+    /// Install `fd` into the registered file table and close it, so that the fixed
+    /// slot is the only reference left. `fd` is closed if that fails too.
+    pub fn register(fd: RawFd) -> io::Result<FixedFd> {
+        let fixed = RUNTIME.with(|runtime| runtime.driver.uring()?.register_fd(fd));
+        close_raw(fd);
+        fixed
+    }
+}
+
+/// The descriptor an op is issued on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UringFd {
+    Raw(RawFd),
+    Fixed(FixedFd),
+}
+
+impl UringFd {
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, UringFd::Fixed(_))
+    }
+
+    /// Move a raw descriptor into the registered file table, see `FixedFd::register`.
+    pub fn into_fixed(self) -> io::Result<FixedFd> {
+        match self {
+            UringFd::Raw(fd) => FixedFd::register(fd),
+            UringFd::Fixed(fixed) => Ok(fixed),
+        }
+    }
+
+    /// Close the descriptor and, for a fixed one, give its slot back.
+    pub(crate) async fn close(self) -> io::Result<()> {
+        let completion = Op::close(self).await;
+        if let UringFd::Fixed(fixed) = self {
+            RUNTIME.with(|runtime| runtime.driver.free_fixed(fixed));
+        }
+        completion.result.map(|_| ())
+    }
+
+    /// Close the descriptor from a `Drop`, where nobody can wait for it. Inside a
+    /// runtime a task awaits the `Close` op, outside of one a raw fd is closed with
+    /// `close_raw` and a fixed slot goes away with the ring.
+    pub(crate) fn close_detached(self) {
+        if RUNTIME.is_set() {
+            spawn(async move {
+                let _ = self.close().await;
+            });
+        } else if let UringFd::Raw(fd) = self {
+            close_raw(fd);
+        }
+    }
+}
+
+/// `close(2)` a descriptor without going through the driver. A mock fd is not in
+/// the process' table, it goes away with its `MockDriver`.
+pub(crate) fn close_raw(fd: RawFd) {
+    if !is_mock_fd(fd) {
+        unsafe { libc::close(fd) };
+    }
+}
+
+impl From<RawFd> for UringFd {
+    fn from(fd: RawFd) -> Self {
+        UringFd::Raw(fd)
+    }
+}
+
+impl From<FixedFd> for UringFd {
+    fn from(fd: FixedFd) -> Self {
+        UringFd::Fixed(fd)
+    }
+}
+
+/// Build an SQE from a `UringFd`, binding `$f` to either `types::Fd` or `types::Fixed`.
+macro_rules! with_fd {
+    ($fd:expr, |$f:ident| $build:expr) => {
+        match $fd {
+            $crate::driver::fd::UringFd::Raw(fd) => {
+                let $f = io_uring::types::Fd(fd);
+                $build
+            }
+            $crate::driver::fd::UringFd::Fixed(fixed) => {
+                let $f = io_uring::types::Fixed(fixed.slot());
+                $build
+            }
+        }
+    };
+}
+
+pub(crate) use with_fd;
+
+/// Bookkeeping of the free slots of a sparse registered file table.
+pub(crate) struct FileTable {
+    free: Vec<u32>,
+}
+
+impl FileTable {
+    pub fn new(nr: u32) -> Self {
+        Self {
+            free: (0..nr).rev().collect(),
+        }
+    }
+
+    pub fn alloc(&mut self) -> io::Result<FixedFd> {
+        self.free.pop().map(FixedFd).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::OutOfMemory, "registered file table is full")
+        })
+    }
+
+    pub fn free(&mut self, fd: FixedFd) {
+        self.free.push(fd.0);
+    }
+}
@@ -0,0 +1,747 @@
+use std::cell::{RefCell, UnsafeCell};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{CStr, OsStr};
+use std::io;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use io_uring::{opcode, squeue};
+
+use super::Driver;
+use super::fault::{FaultRule, Faults, Injected};
+use super::op::{Cqe, Op, OpStage, UringOp};
+use super::sqe::Sqe;
+use crate::utils::IdGenerator;
+
+/// Mock fds are handed out from here up, far above anything the process' own fd
+/// table gets to, so that one is never mistaken for a real descriptor.
+pub(crate) const MOCK_FD_BASE: RawFd = 1 << 30;
+
+pub(crate) fn is_mock_fd(fd: RawFd) -> bool {
+    fd >= MOCK_FD_BASE
+}
+
+/// An in-process driver for deterministic tests.
+///
+/// Ops run against in-memory files and stream pairs instead of the kernel. Which
+/// of the due completions is delivered next is picked by a PRNG seeded at
+/// creation, so a failing run can be replayed from its seed. Completions are due
+/// on a virtual clock, which only moves forward by injected delays and the
+/// configured latency.
+///
+/// Parking while every op in flight waits on data nobody is going to provide
+/// panics, instead of hanging the test.
+pub struct MockDriver {
+    inner: UnsafeCell<MockInner>,
+}
+
+impl MockDriver {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            inner: UnsafeCell::new(MockInner::new(seed)),
+        }
+    }
+
+    /// Delay every completion by a random latency of up to `max`.
+    pub fn latency(&self, max: Duration) {
+        unsafe { (*self.inner.get()).latency = max }
+    }
+
+    pub fn inject(&self, rule: FaultRule) {
+        unsafe { (*self.inner.get()).faults.push(rule) }
+    }
+
+    pub fn clear_faults(&self) {
+        unsafe { (*self.inner.get()).faults.clear() }
+    }
+
+    /// The virtual time elapsed since the driver was created.
+    pub fn now(&self) -> Duration {
+        unsafe { (*self.inner.get()).now }
+    }
+
+    /// A connected pair of in-memory byte streams, e.g. for `TcpStream::from_raw_fd`.
+    pub fn stream_pair(&self) -> (RawFd, RawFd) {
+        unsafe { (*self.inner.get()).stream_pair() }
+    }
+
+    /// Create or replace the in-memory file at `path`.
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let contents = Rc::new(RefCell::new(contents.into()));
+        unsafe {
+            (*self.inner.get())
+                .files
+                .insert(path.as_ref().into(), contents)
+        };
+    }
+
+    /// The contents of the in-memory file at `path`.
+    pub fn file(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        let files = unsafe { &(*self.inner.get()).files };
+        files.get(path.as_ref()).map(|data| data.borrow().clone())
+    }
+
+    pub(crate) fn begin_chain(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).begin_chain() }
+    }
+
+    pub(crate) fn end_chain(&self, hard: bool, commit: bool) {
+        unsafe { (*self.inner.get()).end_chain(hard, commit) }
+    }
+}
+
+impl Driver for MockDriver {
+    fn submit_op<T: UringOp>(&self, data: T) -> Op<T> {
+        unsafe { (*self.inner.get()).submit_op(data) }
+    }
+
+    fn poll_op<T: UringOp>(&self, op: &mut Op<T>, cx: &mut Context<'_>) -> Poll<Cqe> {
+        unsafe { super::poll_op_stage(&mut (*self.inner.get()).ops, op.id, cx) }
+    }
+
+    fn cancel_op(&self, id: u64) -> io::Result<()> {
+        unsafe { (*self.inner.get()).cancel(id) };
+        Ok(())
+    }
+
+    fn detach_op(&self, id: u64, data: Box<dyn UringOp>) {
+        let inner = unsafe { &mut *self.inner.get() };
+        if super::detach_op_stage(&mut inner.ops, id, data) {
+            inner.cancel(id);
+        }
+    }
+
+    fn park(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).park() }
+        Ok(())
+    }
+
+    fn supports(&self, opcode: u8) -> bool {
+        matches!(
+            opcode,
+            opcode::Read::CODE
+                | opcode::ReadFixed::CODE
+                | opcode::Recv::CODE
+                | opcode::Readv::CODE
+                | opcode::RecvMsg::CODE
+                | opcode::Write::CODE
+                | opcode::WriteFixed::CODE
+                | opcode::Send::CODE
+                | opcode::Writev::CODE
+                | opcode::SendMsg::CODE
+                | opcode::OpenAt::CODE
+                | opcode::OpenAt2::CODE
+                | opcode::Close::CODE
+                | opcode::Fsync::CODE
+                | opcode::SyncFileRange::CODE
+                | opcode::Fallocate::CODE
+                | opcode::Ftruncate::CODE
+                | opcode::Statx::CODE
+                | opcode::AsyncCancel::CODE
+                // Claimed so that nothing falls back to a real syscall, `perform`
+                // refuses them as there is no network to reach.
+                | opcode::Socket::CODE
+                | opcode::Accept::CODE
+                | opcode::Connect::CODE
+        )
+    }
+}
+
+enum MockFd {
+    File {
+        data: Rc<RefCell<Vec<u8>>>,
+        pos: usize,
+    },
+    Stream {
+        rx: Rc<RefCell<Pipe>>,
+        tx: Rc<RefCell<Pipe>>,
+    },
+}
+
+/// One direction of a stream pair.
+#[derive(Default)]
+struct Pipe {
+    data: VecDeque<u8>,
+    write_closed: bool,
+    read_closed: bool,
+}
+
+/// SQEs run in order, a single op or an `OpChain`.
+struct Job {
+    sqes: VecDeque<(u64, Sqe)>,
+    broken: bool,
+    // the faults of the front SQE, kept while it is blocked
+    injected: Option<Injected>,
+}
+
+impl Job {
+    fn new(sqes: impl IntoIterator<Item = (u64, Sqe)>) -> Self {
+        Self {
+            sqes: sqes.into_iter().collect(),
+            broken: false,
+            injected: None,
+        }
+    }
+}
+
+struct MockInner {
+    ops: HashMap<u64, OpStage>,
+    id_generator: IdGenerator,
+    rng: u64,
+    now: Duration,
+    latency: Duration,
+    faults: Faults,
+    files: HashMap<PathBuf, Rc<RefCell<Vec<u8>>>>,
+    fds: HashMap<RawFd, MockFd>,
+    next_fd: RawFd,
+    // jobs that cannot go on yet, e.g. reading an empty stream
+    blocked: Vec<Job>,
+    // results with the virtual time they are due at
+    due: Vec<(Duration, u64, i32)>,
+    chain: Option<Vec<(u64, Sqe)>>,
+}
+
+impl MockInner {
+    fn new(seed: u64) -> Self {
+        Self {
+            ops: HashMap::new(),
+            id_generator: IdGenerator::new(),
+            // xorshift must not start from zero
+            rng: seed.wrapping_add(0x9e37_79b9_7f4a_7c15) | 1,
+            now: Duration::ZERO,
+            latency: Duration::ZERO,
+            faults: Faults::default(),
+            files: HashMap::new(),
+            fds: HashMap::new(),
+            next_fd: MOCK_FD_BASE,
+            blocked: Vec::new(),
+            due: Vec::new(),
+            chain: None,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        // xorshift64*
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn jitter(&mut self) -> Duration {
+        if self.latency.is_zero() {
+            return Duration::ZERO;
+        }
+        let max = self.latency.as_nanos() as u64;
+        Duration::from_nanos(self.next_random() % (max + 1))
+    }
+
+    fn alloc_fd(&mut self, fd: MockFd) -> RawFd {
+        let raw = self.next_fd;
+        self.next_fd += 1;
+        self.fds.insert(raw, fd);
+        raw
+    }
+
+    fn stream_pair(&mut self) -> (RawFd, RawFd) {
+        let a_to_b = Rc::new(RefCell::new(Pipe::default()));
+        let b_to_a = Rc::new(RefCell::new(Pipe::default()));
+        let a = self.alloc_fd(MockFd::Stream {
+            rx: b_to_a.clone(),
+            tx: a_to_b.clone(),
+        });
+        let b = self.alloc_fd(MockFd::Stream {
+            rx: a_to_b,
+            tx: b_to_a,
+        });
+        (a, b)
+    }
+
+    fn submit_op<T: UringOp>(&mut self, data: T) -> Op<T> {
+        let id = self.id_generator.gen_id();
+        let mut op = Op::new(id, data);
+
+        let sqe = Sqe::new(op.build_sqe());
+        self.ops.insert(id, OpStage::Submitted);
+        match self.chain {
+            Some(ref mut chain) => chain.push((id, sqe)),
+            None => self.start(Job::new([(id, sqe)])),
+        }
+        op
+    }
+
+    fn begin_chain(&mut self) -> io::Result<()> {
+        if self.chain.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "op chains cannot be nested",
+            ));
+        }
+        self.chain = Some(Vec::new());
+        Ok(())
+    }
+
+    fn end_chain(&mut self, hard: bool, commit: bool) {
+        let chain = self.chain.take().unwrap_or_default();
+        if !commit {
+            for (id, _) in chain {
+                self.ops.remove(&id);
+            }
+            return;
+        }
+
+        let flag = if hard {
+            squeue::Flags::IO_HARDLINK
+        } else {
+            squeue::Flags::IO_LINK
+        };
+        let len = chain.len();
+        let sqes = chain.into_iter().enumerate().map(|(i, (id, mut sqe))| {
+            if i + 1 < len {
+                sqe.flags |= flag.bits();
+            }
+            (id, sqe)
+        });
+        self.start(Job::new(sqes));
+    }
+
+    fn start(&mut self, mut job: Job) {
+        if !self.run(&mut job) {
+            self.blocked.push(job);
+        }
+    }
+
+    /// Run a job as far as possible, `false` if it is blocked.
+    fn run(&mut self, job: &mut Job) -> bool {
+        while let Some(&(id, sqe)) = job.sqes.front() {
+            let (result, delay) = if job.broken {
+                (-libc::ECANCELED, Duration::ZERO)
+            } else {
+                let injected = *job.injected.get_or_insert_with(|| self.faults.inject(&sqe));
+                let mut short_sqe = sqe;
+                let shortened = injected.short.and_then(|short| short_sqe.shorten(short));
+                let Some(result) = self.perform(&short_sqe) else {
+                    return false;
+                };
+                if let Some(shortened) = shortened {
+                    shortened.finish();
+                }
+                let result = match injected.error {
+                    Some(errno) => {
+                        if sqe.creates_fd() && result >= 0 {
+                            self.close(result);
+                        }
+                        -errno
+                    }
+                    None => result,
+                };
+                (result, injected.delay)
+            };
+
+            job.sqes.pop_front();
+            job.injected = None;
+            job.broken = job.broken || sqe.breaks_link(result);
+            let due = self.now + delay + self.jitter();
+            self.due.push((due, id, result));
+        }
+        true
+    }
+
+    /// Perform an op in memory, `None` if it has to wait for a stream.
+    fn perform(&mut self, sqe: &Sqe) -> Option<i32> {
+        if sqe.unsupported() {
+            return Some(-libc::EOPNOTSUPP);
+        }
+        match sqe.opcode {
+            opcode::Read::CODE
+            | opcode::ReadFixed::CODE
+            | opcode::Recv::CODE
+            | opcode::Readv::CODE
+            | opcode::RecvMsg::CODE => self.read(sqe),
+            opcode::Write::CODE
+            | opcode::WriteFixed::CODE
+            | opcode::Send::CODE
+            | opcode::Writev::CODE
+            | opcode::SendMsg::CODE => Some(self.write(sqe)),
+            opcode::OpenAt::CODE | opcode::OpenAt2::CODE => Some(self.open(sqe)),
+            opcode::Close::CODE => Some(self.close(sqe.fd)),
+            opcode::Fsync::CODE
+            | opcode::SyncFileRange::CODE
+            | opcode::Fallocate::CODE
+            | opcode::Ftruncate::CODE => Some(self.resize(sqe)),
+            opcode::Statx::CODE => Some(self.statx(sqe)),
+            opcode::AsyncCancel::CODE => Some(self.cancel(sqe.addr)),
+            _ => Some(-libc::EOPNOTSUPP),
+        }
+    }
+
+    fn read(&mut self, sqe: &Sqe) -> Option<i32> {
+        let iovecs = sqe.iovecs();
+        let mut buf = vec![0; iovecs.iter().map(|iov| iov.iov_len).sum()];
+        let n = self.read_into(sqe, &mut buf)?;
+        let mut read = &buf[..n.max(0) as usize];
+        for iov in iovecs {
+            let len = iov.iov_len.min(read.len());
+            // Safety: the op owns the buffers until it completes.
+            unsafe { std::ptr::copy_nonoverlapping(read.as_ptr(), iov.iov_base as *mut u8, len) };
+            read = &read[len..];
+        }
+        Some(n)
+    }
+
+    fn read_into(&mut self, sqe: &Sqe, buf: &mut [u8]) -> Option<i32> {
+        match self.fds.get_mut(&sqe.fd) {
+            None => Some(-libc::EBADF),
+            Some(MockFd::File { data, pos }) => {
+                let data = data.borrow();
+                let offset = if sqe.off == u64::MAX {
+                    *pos
+                } else {
+                    sqe.off as usize
+                };
+                let offset = offset.min(data.len());
+                let n = (data.len() - offset).min(buf.len());
+                buf[..n].copy_from_slice(&data[offset..offset + n]);
+                if sqe.off == u64::MAX {
+                    *pos += n;
+                }
+                Some(n as i32)
+            }
+            Some(MockFd::Stream { rx, .. }) => {
+                let mut rx = rx.borrow_mut();
+                if rx.data.is_empty() {
+                    return rx.write_closed.then_some(0);
+                }
+                let n = rx.data.len().min(buf.len());
+                for (dst, src) in buf.iter_mut().zip(rx.data.drain(..n)) {
+                    *dst = src;
+                }
+                Some(n as i32)
+            }
+        }
+    }
+
+    fn write(&mut self, sqe: &Sqe) -> i32 {
+        let mut buf = Vec::new();
+        for iov in sqe.iovecs() {
+            // Safety: the op owns the buffers until it completes.
+            buf.extend_from_slice(unsafe {
+                std::slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len)
+            });
+        }
+        let buf = &buf[..];
+        match self.fds.get_mut(&sqe.fd) {
+            None => -libc::EBADF,
+            Some(MockFd::File { data, pos }) => {
+                let mut data = data.borrow_mut();
+                let offset = if sqe.off == u64::MAX {
+                    *pos
+                } else {
+                    sqe.off as usize
+                };
+                if data.len() < offset + buf.len() {
+                    data.resize(offset + buf.len(), 0);
+                }
+                data[offset..offset + buf.len()].copy_from_slice(buf);
+                if sqe.off == u64::MAX {
+                    *pos += buf.len();
+                }
+                buf.len() as i32
+            }
+            Some(MockFd::Stream { tx, .. }) => {
+                let mut tx = tx.borrow_mut();
+                if tx.read_closed {
+                    return -libc::EPIPE;
+                }
+                tx.data.extend(buf);
+                buf.len() as i32
+            }
+        }
+    }
+
+    /// Ops changing the size of a file. Syncing has nothing to do in memory.
+    fn resize(&mut self, sqe: &Sqe) -> i32 {
+        let Some(MockFd::File { data, .. }) = self.fds.get_mut(&sqe.fd) else {
+            return -libc::EBADF;
+        };
+        let mut data = data.borrow_mut();
+        match sqe.opcode {
+            opcode::Ftruncate::CODE => data.resize(sqe.off as usize, 0),
+            opcode::Fallocate::CODE => {
+                let (offset, len) = (sqe.off as usize, sqe.addr as usize);
+                let mode = sqe.len as i32;
+                if mode & libc::FALLOC_FL_PUNCH_HOLE != 0 {
+                    let end = (offset + len).min(data.len());
+                    if offset < end {
+                        data[offset..end].fill(0);
+                    }
+                } else if mode & libc::FALLOC_FL_KEEP_SIZE == 0 && data.len() < offset + len {
+                    data.resize(offset + len, 0);
+                }
+            }
+            _ => {}
+        }
+        0
+    }
+
+    /// The size and type of an in-memory file, the rest is left out.
+    fn statx(&mut self, sqe: &Sqe) -> i32 {
+        // Safety: the op owns the path until it completes.
+        let path = unsafe { CStr::from_ptr(sqe.addr as *const libc::c_char) };
+        let data = if path.is_empty() && sqe.op_flags as i32 & libc::AT_EMPTY_PATH != 0 {
+            match self.fds.get(&sqe.fd) {
+                Some(MockFd::File { data, .. }) => data.clone(),
+                Some(MockFd::Stream { .. }) => return -libc::EOPNOTSUPP,
+                None => return -libc::EBADF,
+            }
+        } else if sqe.fd == libc::AT_FDCWD {
+            let path = PathBuf::from(OsStr::from_bytes(path.to_bytes()));
+            match self.files.get(&path) {
+                Some(data) => data.clone(),
+                None => return -libc::ENOENT,
+            }
+        } else {
+            return -libc::EOPNOTSUPP;
+        };
+        // Safety: the op owns the statx buffer until it completes.
+        let statx = unsafe { &mut *(sqe.off as *mut libc::statx) };
+        statx.stx_mask = libc::STATX_TYPE | libc::STATX_MODE | libc::STATX_NLINK | libc::STATX_SIZE;
+        statx.stx_mode = (libc::S_IFREG | 0o644) as u16;
+        statx.stx_nlink = 1;
+        statx.stx_size = data.borrow().len() as u64;
+        statx.stx_blksize = 4096;
+        0
+    }
+
+    fn open(&mut self, sqe: &Sqe) -> i32 {
+        if sqe.fd != libc::AT_FDCWD {
+            return -libc::EOPNOTSUPP;
+        }
+        // Safety: the op owns the path until it completes.
+        let path = unsafe { CStr::from_ptr(sqe.addr as *const libc::c_char) };
+        let path = PathBuf::from(OsStr::from_bytes(path.to_bytes()));
+        // The resolve flags of `openat2` are ignored, the in-memory files have no
+        // links or directories to restrict.
+        let flags = if sqe.opcode == opcode::OpenAt2::CODE {
+            // Safety: the op owns its `open_how` until it completes.
+            unsafe { (*(sqe.off as *const libc::open_how)).flags as i32 }
+        } else {
+            sqe.op_flags as i32
+        };
+        let data = match self.files.get(&path) {
+            Some(_) if flags & libc::O_CREAT != 0 && flags & libc::O_EXCL != 0 => {
+                return -libc::EEXIST;
+            }
+            Some(data) => data.clone(),
+            None if flags & libc::O_CREAT != 0 => {
+                let data = Rc::new(RefCell::new(Vec::new()));
+                self.files.insert(path, data.clone());
+                data
+            }
+            None => return -libc::ENOENT,
+        };
+        if flags & libc::O_TRUNC != 0 {
+            data.borrow_mut().clear();
+        }
+        self.alloc_fd(MockFd::File { data, pos: 0 })
+    }
+
+    fn close(&mut self, fd: RawFd) -> i32 {
+        match self.fds.remove(&fd) {
+            Some(MockFd::Stream { rx, tx }) => {
+                rx.borrow_mut().read_closed = true;
+                tx.borrow_mut().write_closed = true;
+                0
+            }
+            Some(MockFd::File { .. }) => 0,
+            None => -libc::EBADF,
+        }
+    }
+
+    /// Cancel a blocked op, along with the rest of its chain.
+    fn cancel(&mut self, target: u64) -> i32 {
+        let Some(pos) = self
+            .blocked
+            .iter()
+            .position(|job| job.sqes.front().is_some_and(|&(id, _)| id == target))
+        else {
+            return -libc::ENOENT;
+        };
+        let job = self.blocked.remove(pos);
+        for (id, _) in job.sqes {
+            self.due.push((self.now, id, -libc::ECANCELED));
+        }
+        0
+    }
+
+    fn complete(&mut self, id: u64, result: i32) {
+        super::complete_op(&mut self.ops, id, Cqe::new(result, 0));
+    }
+
+    /// Deliver one completion, advancing the clock to when it is due.
+    fn park(&mut self) {
+        if self.blocked.is_empty() && self.due.is_empty() {
+            return;
+        }
+
+        for job in std::mem::take(&mut self.blocked) {
+            self.start(job);
+        }
+        if self.due.is_empty() {
+            panic!(
+                "mock driver deadlock: {} blocked ops can never complete",
+                self.blocked.len()
+            );
+        }
+
+        let earliest = self.due.iter().map(|&(due, ..)| due).min().unwrap();
+        self.now = self.now.max(earliest);
+        let ready: Vec<_> = (0..self.due.len())
+            .filter(|&i| self.due[i].0 <= self.now)
+            .collect();
+        let pick = ready[(self.next_random() % ready.len() as u64) as usize];
+        let (_, id, result) = self.due.swap_remove(pick);
+        self.complete(id, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::FromRawFd;
+    use std::time::Duration;
+
+    use io_uring::opcode;
+
+    use crate::driver::{Fault, FaultRule};
+    use crate::fs::{File, OpenOptions};
+    use crate::net::{FixedTcpStream, TcpStream};
+    use crate::runtime::{DriverKind, Runtime};
+
+    fn runtime() -> Runtime {
+        Runtime::builder()
+            .driver(DriverKind::Mock { seed: 7 })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn reset() {
+        let runtime = runtime();
+        let mock = runtime.driver.mock().unwrap();
+        let (client, server) = mock.stream_pair();
+        mock.inject(
+            FaultRule::new(Fault::Error(libc::ECONNRESET))
+                .opcode(opcode::Send::CODE)
+                .fd(client)
+                .times(1),
+        );
+        runtime.block_on(async move {
+            let client = unsafe { TcpStream::from_raw_fd(client) };
+            let _server = unsafe { TcpStream::from_raw_fd(server) };
+            let err = client.write(b"ping".to_vec()).await.0.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::ConnectionReset);
+            // The rule is used up.
+            assert_eq!(client.write(b"pong".to_vec()).await.0.unwrap(), 4);
+        });
+    }
+
+    #[test]
+    fn short() {
+        let runtime = runtime();
+        let mock = runtime.driver.mock().unwrap();
+        let (client, server) = mock.stream_pair();
+        mock.inject(FaultRule::new(Fault::Short(4)).times(1));
+        runtime.block_on(async move {
+            let client = unsafe { TcpStream::from_raw_fd(client) };
+            let server = unsafe { TcpStream::from_raw_fd(server) };
+            assert_eq!(client.write(b"ping pong".to_vec()).await.0.unwrap(), 4);
+            let (res, buf) = server.read(Vec::with_capacity(16)).await;
+            assert_eq!(&buf[..res.unwrap()], b"ping");
+        });
+    }
+
+    #[test]
+    fn short_only_matches_transfers() {
+        let runtime = runtime();
+        let mock = runtime.driver.mock().unwrap();
+        mock.add_file("short.txt", "hello world");
+        mock.inject(FaultRule::new(Fault::Short(3)).times(1));
+        runtime.block_on(async {
+            // The open does not use up the rule, the read does.
+            let file = File::open("short.txt").await.unwrap();
+            let (res, buf) = file.read_at(Vec::with_capacity(16), 0).await;
+            assert_eq!(&buf[..res.unwrap()], b"hel");
+            let (res, buf) = file.read_at(Vec::with_capacity(16), 0).await;
+            assert_eq!(&buf[..res.unwrap()], b"hello world");
+        });
+    }
+
+    #[test]
+    fn short_vectored() {
+        let runtime = runtime();
+        let mock = runtime.driver.mock().unwrap();
+        mock.inject(FaultRule::new(Fault::Short(8)).opcode(opcode::Writev::CODE));
+        runtime.block_on(async {
+            let file = File::create("vectored.txt").await.unwrap();
+            let bufs = vec![b"hello ".to_vec(), b"world".to_vec()];
+            let (res, _) = file.write_vectored_at(bufs, 0).await;
+            assert_eq!(res.unwrap(), 8);
+        });
+        assert_eq!(mock.file("vectored.txt").unwrap(), b"hello wo");
+    }
+
+    #[test]
+    fn delay() {
+        let runtime = runtime();
+        let mock = runtime.driver.mock().unwrap();
+        mock.add_file("delay.txt", "hello");
+        mock.inject(
+            FaultRule::new(Fault::Delay(Duration::from_millis(50)))
+                .opcode(opcode::Read::CODE)
+                .times(1),
+        );
+        runtime.block_on(async {
+            let file = File::open("delay.txt").await.unwrap();
+            let before = runtime.driver.mock().unwrap().now();
+            let (res, buf) = file.read_at(Vec::with_capacity(16), 0).await;
+            assert_eq!(&buf[..res.unwrap()], b"hello");
+            assert!(runtime.driver.mock().unwrap().now() - before >= Duration::from_millis(50));
+        });
+    }
+
+    #[test]
+    fn openat2() {
+        let runtime = runtime();
+        let mock = runtime.driver.mock().unwrap();
+        runtime.block_on(async {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .resolve(libc::RESOLVE_BENEATH)
+                .open("beneath.txt")
+                .await
+                .unwrap();
+            file.write_all(b"hello".to_vec()).await.0.unwrap();
+        });
+        assert_eq!(mock.file("beneath.txt").unwrap(), b"hello");
+    }
+
+    #[test]
+    fn no_network() {
+        let runtime = runtime();
+        runtime.block_on(async {
+            // Refused by the mock, instead of creating a real socket it cannot close.
+            let err = TcpStream::connect("127.0.0.1:1").await.err().unwrap();
+            assert_eq!(err.raw_os_error(), Some(libc::EOPNOTSUPP));
+            // There is no registered file table outside io_uring.
+            let err = FixedTcpStream::connect("127.0.0.1:1").await.err().unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        });
+    }
+}
@@ -1,13 +1,371 @@
 use crate::utils::IdGenerator;
-use io_uring::IoUring;
+use io_uring::{IoUring, Probe, cqueue, squeue};
 use std::cell::UnsafeCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io;
-use std::task::{Context, Poll};
+use std::os::fd::{AsRawFd, RawFd};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
 
+mod blocking;
+pub mod chain;
+pub mod epoll;
+pub mod fault;
+pub mod fd;
+pub mod mock;
 pub mod op;
+mod ring;
+mod sqe;
 
+use blocking::BlockingPool;
+pub use chain::OpChain;
+pub use epoll::EpollDriver;
+pub use fault::{Fault, FaultRule};
+use fault::{Faults, Injected};
+use fd::{FileTable, FixedFd, close_raw};
+pub use mock::MockDriver;
 use op::*;
+use ring::Ring;
+use sqe::{Shortened, Sqe};
+
+const DEFAULT_ENTRIES: u32 = 100;
+
+/// Setup parameters of the io_uring instance owned by a `UringDriver`.
+#[derive(Clone, Debug)]
+pub struct UringConfig {
+    entries: u32,
+    sqpoll_idle: Option<u32>,
+    sqpoll_cpu: Option<u32>,
+    attach_wq: Option<RawFd>,
+    big_cqe: bool,
+}
+
+impl UringConfig {
+    pub fn new() -> Self {
+        Self {
+            entries: DEFAULT_ENTRIES,
+            sqpoll_idle: None,
+            sqpoll_cpu: None,
+            attach_wq: None,
+            big_cqe: false,
+        }
+    }
+
+    pub fn entries(mut self, entries: u32) -> Self {
+        self.entries = entries;
+        self
+    }
+
+    /// Enable `IORING_SETUP_SQPOLL`. The kernel poller thread goes to sleep after
+    /// `idle` milliseconds without new submissions.
+    pub fn sqpoll(mut self, idle: u32) -> Self {
+        self.sqpoll_idle = Some(idle);
+        self
+    }
+
+    /// Bind the SQPOLL poller thread to `cpu`. Requires `sqpoll`.
+    pub fn sqpoll_cpu(mut self, cpu: u32) -> Self {
+        self.sqpoll_cpu = Some(cpu);
+        self
+    }
+
+    /// Share the async backend (and, with `sqpoll`, the poller thread) of the
+    /// ring referred to by `fd`, see `IORING_SETUP_ATTACH_WQ`.
+    pub fn attach_wq(mut self, fd: RawFd) -> Self {
+        self.attach_wq = Some(fd);
+        self
+    }
+
+    /// Use 32 byte CQEs (`IORING_SETUP_CQE32`), whose extra payload ops such as
+    /// `IORING_OP_URING_CMD` fill in. It shows up in `Completion::big_cqe`.
+    pub fn big_cqe(mut self, enable: bool) -> Self {
+        self.big_cqe = enable;
+        self
+    }
+
+    fn build(&self) -> io::Result<Ring> {
+        if self.big_cqe {
+            Ok(Ring::Big(self.setup()?))
+        } else {
+            Ok(Ring::Normal(self.setup()?))
+        }
+    }
+
+    fn setup<S: squeue::EntryMarker, C: cqueue::EntryMarker>(&self) -> io::Result<IoUring<S, C>> {
+        let mut builder = IoUring::<S, C>::builder();
+        match (self.sqpoll_idle, self.sqpoll_cpu) {
+            (Some(idle), cpu) => {
+                builder.setup_sqpoll(idle);
+                if let Some(cpu) = cpu {
+                    builder.setup_sqpoll_cpu(cpu);
+                }
+            }
+            (None, Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "sqpoll_cpu requires sqpoll to be enabled",
+                ));
+            }
+            (None, None) => {}
+        }
+        if let Some(fd) = self.attach_wq {
+            builder.setup_attach_wq(fd);
+        }
+        builder.build(self.entries)
+    }
+}
+
+impl Default for UringConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The backend a runtime submits its ops to.
+pub trait Driver {
+    /// Hand an op over to the backend. An op that cannot be submitted completes
+    /// with the error, so its data always comes back through the completion.
+    fn submit_op<T: UringOp>(&self, data: T) -> Op<T>;
+
+    fn poll_op<T: UringOp>(&self, op: &mut Op<T>, cx: &mut Context<'_>) -> Poll<Cqe>;
+
+    /// Ask for op `id` to be canceled. It still completes, with `ECANCELED` if the
+    /// cancellation won.
+    fn cancel_op(&self, id: u64) -> io::Result<()>;
+
+    /// Take over the data of a dropped op, keeping it alive until the op completes.
+    fn detach_op(&self, id: u64, data: Box<dyn UringOp>);
+
+    /// Flush submitted ops and block until at least one of them completes, if any
+    /// is in flight.
+    fn park(&self) -> io::Result<()>;
+
+    /// Whether the backend performs ops with this opcode (`opcode::*::CODE`). The
+    /// io_uring driver answers for the running kernel, and runs the unsupported ops
+    /// it knows how to emulate on a blocking pool.
+    fn supports(&self, opcode: u8) -> bool;
+}
+
+/// The driver of a `Runtime`, either backend.
+// There is one per runtime, boxing would only add an indirection to every op.
+#[allow(clippy::large_enum_variant)]
+pub enum RuntimeDriver {
+    Uring(UringDriver),
+    Epoll(EpollDriver),
+    Mock(MockDriver),
+}
+
+impl RuntimeDriver {
+    pub fn is_uring(&self) -> bool {
+        matches!(self, RuntimeDriver::Uring(_))
+    }
+
+    /// The io_uring driver, for features the epoll driver has no equivalent of.
+    pub fn uring(&self) -> io::Result<&UringDriver> {
+        match self {
+            RuntimeDriver::Uring(driver) => Ok(driver),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only supported by the io_uring driver",
+            )),
+        }
+    }
+
+    /// The mock driver, to set up its files, streams and faults.
+    pub fn mock(&self) -> io::Result<&MockDriver> {
+        match self {
+            RuntimeDriver::Mock(driver) => Ok(driver),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "not a mock driver",
+            )),
+        }
+    }
+
+    /// Release the slot of a `FixedFd`, which only the io_uring driver hands out.
+    pub(crate) fn free_fixed(&self, fd: FixedFd) {
+        if let RuntimeDriver::Uring(driver) = self {
+            driver.free_fixed(fd);
+        }
+    }
+
+    pub(crate) fn begin_chain(&self) -> io::Result<()> {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.begin_chain(),
+            RuntimeDriver::Epoll(driver) => driver.begin_chain(),
+            RuntimeDriver::Mock(driver) => driver.begin_chain(),
+        }
+    }
+
+    pub(crate) fn end_chain(&self, hard: bool, commit: bool) -> io::Result<()> {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.end_chain(hard, commit),
+            RuntimeDriver::Epoll(driver) => driver.end_chain(hard, commit),
+            RuntimeDriver::Mock(driver) => {
+                driver.end_chain(hard, commit);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Driver for RuntimeDriver {
+    fn submit_op<T: UringOp>(&self, data: T) -> Op<T> {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.submit_op(data),
+            RuntimeDriver::Epoll(driver) => driver.submit_op(data),
+            RuntimeDriver::Mock(driver) => driver.submit_op(data),
+        }
+    }
+
+    fn poll_op<T: UringOp>(&self, op: &mut Op<T>, cx: &mut Context<'_>) -> Poll<Cqe> {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.poll_op(op, cx),
+            RuntimeDriver::Epoll(driver) => driver.poll_op(op, cx),
+            RuntimeDriver::Mock(driver) => driver.poll_op(op, cx),
+        }
+    }
+
+    fn cancel_op(&self, id: u64) -> io::Result<()> {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.cancel_op(id),
+            RuntimeDriver::Epoll(driver) => driver.cancel_op(id),
+            RuntimeDriver::Mock(driver) => driver.cancel_op(id),
+        }
+    }
+
+    fn detach_op(&self, id: u64, data: Box<dyn UringOp>) {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.detach_op(id, data),
+            RuntimeDriver::Epoll(driver) => driver.detach_op(id, data),
+            RuntimeDriver::Mock(driver) => driver.detach_op(id, data),
+        }
+    }
+
+    fn park(&self) -> io::Result<()> {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.park(),
+            RuntimeDriver::Epoll(driver) => driver.park(),
+            RuntimeDriver::Mock(driver) => driver.park(),
+        }
+    }
+
+    fn supports(&self, opcode: u8) -> bool {
+        match self {
+            RuntimeDriver::Uring(driver) => driver.supports(opcode),
+            RuntimeDriver::Epoll(driver) => driver.supports(opcode),
+            RuntimeDriver::Mock(driver) => driver.supports(opcode),
+        }
+    }
+}
+
+impl From<UringDriver> for RuntimeDriver {
+    fn from(driver: UringDriver) -> Self {
+        RuntimeDriver::Uring(driver)
+    }
+}
+
+impl From<EpollDriver> for RuntimeDriver {
+    fn from(driver: EpollDriver) -> Self {
+        RuntimeDriver::Epoll(driver)
+    }
+}
+
+impl From<MockDriver> for RuntimeDriver {
+    fn from(driver: MockDriver) -> Self {
+        RuntimeDriver::Mock(driver)
+    }
+}
+
+/// Poll a single-shot op of `ops`, removing it once completed.
+fn poll_op_stage(ops: &mut HashMap<u64, OpStage>, id: u64, cx: &mut Context<'_>) -> Poll<Cqe> {
+    match ops.get_mut(&id) {
+        Some(op_stage) => match op_stage {
+            OpStage::Submitted => {
+                *op_stage = OpStage::Waiting(cx.waker().clone());
+                return Poll::Pending;
+            }
+            OpStage::Waiting(waker) => {
+                if !waker.will_wake(cx.waker()) {
+                    *waker = cx.waker().clone();
+                }
+                return Poll::Pending;
+            }
+            _ => {}
+        },
+        None => panic!(),
+    }
+
+    match ops.remove(&id) {
+        Some(OpStage::Completed(cqe)) => Poll::Ready(cqe),
+        Some(_) => unreachable!("unexpected stage!"),
+        None => panic!(),
+    }
+}
+
+/// Record a CQE of `id` in its op stage, waking whoever waits for it.
+fn complete_op(ops: &mut HashMap<u64, OpStage>, id: u64, cqe: Cqe) {
+    let more = io_uring::cqueue::more(cqe.flags);
+    let mut finished = false;
+    if let Some(op_stage) = ops.get_mut(&id) {
+        match op_stage {
+            OpStage::Submitted => {
+                *op_stage = OpStage::Completed(cqe);
+            }
+            OpStage::Waiting(waker) => {
+                // This is ok because our runtime is single thread
+                waker.wake_by_ref();
+                *op_stage = OpStage::Completed(cqe);
+            }
+            OpStage::Multishot {
+                completions,
+                waker,
+                terminated,
+            } => {
+                completions.push_back((cqe.result, cqe.flags));
+                *terminated = !more;
+                // Once per batch, the task is polled again anyway.
+                if let Some(waker) = waker.take() {
+                    waker.wake();
+                }
+            }
+            OpStage::Detached(data) => {
+                data.discard(cqe.result, cqe.flags);
+                finished = !more;
+            }
+            OpStage::Completed(..) => unsafe {
+                std::hint::unreachable_unchecked();
+            },
+        }
+    }
+    if finished {
+        ops.remove(&id);
+    }
+}
+
+/// Detach the single-shot op `id`. Returns whether it is still in flight, in
+/// which case its data now waits for the completion in a `Detached` stage.
+fn detach_op_stage(ops: &mut HashMap<u64, OpStage>, id: u64, mut data: Box<dyn UringOp>) -> bool {
+    match ops.remove(&id) {
+        Some(OpStage::Submitted | OpStage::Waiting(_)) => {
+            ops.insert(id, OpStage::Detached(data));
+            true
+        }
+        Some(OpStage::Completed(cqe)) => {
+            data.discard(cqe.result, cqe.flags);
+            false
+        }
+        Some(op_stage) => {
+            ops.insert(id, op_stage);
+            false
+        }
+        None => false,
+    }
+}
+
+// user_data of the poll on the eventfd of the blocking pool
+const BLOCKING_ID: u64 = u64::MAX;
+const BLOCKING_THREADS: usize = 4;
 
 pub struct UringDriver {
     inner: UnsafeCell<UringInner>,
@@ -15,142 +373,787 @@ pub struct UringDriver {
 
 impl UringDriver {
     pub fn new() -> io::Result<Self> {
-        let inner = UringInner::new()?;
+        Self::new_with_config(&UringConfig::new())
+    }
+
+    pub fn new_with_config(config: &UringConfig) -> io::Result<Self> {
+        let inner = UringInner::new(config)?;
         Ok(Self {
             inner: UnsafeCell::new(inner),
         })
     }
 
-    pub fn submit_op<T: UringOp>(&self, data: T) -> io::Result<Op<T>> {
-        unsafe { (*self.inner.get()).submit_op(data) }
+    pub fn is_sqpoll(&self) -> bool {
+        unsafe { (*self.inner.get()).sqpoll }
+    }
+
+    /// Register an empty file table of `nr` slots for `FixedFd`s.
+    pub fn register_files_sparse(&self, nr: u32) -> io::Result<()> {
+        unsafe { (*self.inner.get()).register_files_sparse(nr) }
+    }
+
+    pub fn register_files_update(&self, offset: u32, fds: &[RawFd]) -> io::Result<usize> {
+        unsafe {
+            (*self.inner.get())
+                .uring
+                .submitter()
+                .register_files_update(offset, fds)
+        }
+    }
+
+    pub fn unregister_files(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).unregister_files() }
+    }
+
+    /// Install `fd` into a free slot of the file table. The table takes its own
+    /// reference, `fd` stays open.
+    pub fn register_fd(&self, fd: RawFd) -> io::Result<FixedFd> {
+        let fixed = self.alloc_fixed()?;
+        if let Err(e) = self.register_files_update(fixed.slot(), &[fd]) {
+            self.free_fixed(fixed);
+            return Err(e);
+        }
+        Ok(fixed)
+    }
+
+    /// Remove `fd` from the file table and release its slot.
+    pub fn unregister_fd(&self, fd: FixedFd) -> io::Result<()> {
+        self.register_files_update(fd.slot(), &[-1])?;
+        self.free_fixed(fd);
+        Ok(())
+    }
+
+    /// # Safety
+    ///
+    /// The memory described by `bufs` must stay valid until the buffers are
+    /// unregistered or the driver is dropped.
+    pub unsafe fn register_buffers(&self, bufs: &[libc::iovec]) -> io::Result<()> {
+        unsafe { (*self.inner.get()).uring.submitter().register_buffers(bufs) }
+    }
+
+    pub fn unregister_buffers(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).uring.submitter().unregister_buffers() }
+    }
+
+    /// # Safety
+    ///
+    /// `ring_addr` must point to a page-aligned ring of `entries` buffer descriptors
+    /// that stays valid until `bgid` is unregistered or the driver is dropped.
+    pub unsafe fn register_buf_ring(
+        &self,
+        ring_addr: u64,
+        entries: u16,
+        bgid: u16,
+    ) -> io::Result<()> {
+        unsafe {
+            (*self.inner.get())
+                .uring
+                .submitter()
+                .register_buf_ring_with_flags(ring_addr, entries, bgid, 0)
+        }
+    }
+
+    pub fn unregister_buf_ring(&self, bgid: u16) -> io::Result<()> {
+        unsafe {
+            (*self.inner.get())
+                .uring
+                .submitter()
+                .unregister_buf_ring(bgid)
+        }
+    }
+
+    pub(crate) fn alloc_fixed(&self) -> io::Result<FixedFd> {
+        match unsafe { &mut (*self.inner.get()).files } {
+            Some(files) => files.alloc(),
+            None => Err(io::Error::other("registered file table is not set up")),
+        }
+    }
+
+    /// Release the slot of a `FixedFd` whose file has already left the table.
+    pub(crate) fn free_fixed(&self, fd: FixedFd) {
+        if let Some(files) = unsafe { &mut (*self.inner.get()).files } {
+            files.free(fd);
+        }
+    }
+
+    /// Inject faults into the single-shot ops submitted from now on. Short lengths
+    /// are applied to the SQE, errors and delays to the CQE of the op the kernel
+    /// performed.
+    pub fn inject(&self, rule: FaultRule) {
+        unsafe { (*self.inner.get()).faults.push(rule) }
+    }
+
+    pub fn clear_faults(&self) {
+        unsafe { (*self.inner.get()).faults.clear() }
     }
 
     pub fn submit_and_wait(&self) -> io::Result<()> {
         unsafe { (*self.inner.get()).submit_and_wait() }
     }
 
-    pub fn poll_op<T: UringOp>(
+    pub fn submit_multishot_op<T: UringOp + 'static>(&self, data: T) -> io::Result<MultishotOp<T>> {
+        unsafe { (*self.inner.get()).submit_multishot_op(data) }
+    }
+
+    pub fn poll_multishot_op<T: UringOp + 'static>(
         &self,
-        op: &mut Op<T>,
+        op: &mut MultishotOp<T>,
         cx: &mut Context<'_>,
-    ) -> Poll<io::Result<i32>> {
-        unsafe { (*self.inner.get()).poll_op(op, cx) }
+    ) -> Poll<Option<(io::Result<i32>, u32)>> {
+        unsafe { (*self.inner.get()).poll_multishot_op(op, cx) }
+    }
+
+    pub fn rearm_multishot_op<T: UringOp + 'static>(
+        &self,
+        op: &mut MultishotOp<T>,
+    ) -> io::Result<()> {
+        unsafe { (*self.inner.get()).rearm_multishot_op(op) }
+    }
+
+    pub(crate) fn begin_chain(&self) -> io::Result<()> {
+        unsafe { (*self.inner.get()).begin_chain() }
+    }
+
+    pub(crate) fn end_chain(&self, hard: bool, commit: bool) -> io::Result<()> {
+        unsafe { (*self.inner.get()).end_chain(hard, commit) }
+    }
+
+    /// Hand the resources of a dropped multishot op over to the driver, which cancels
+    /// the op and discards whatever completions are still to come.
+    pub(crate) fn detach_multishot_op(&self, id: u64, data: Box<dyn UringOp>) {
+        unsafe { (*self.inner.get()).detach_multishot_op(id, data) }
+    }
+}
+
+impl Driver for UringDriver {
+    fn submit_op<T: UringOp>(&self, data: T) -> Op<T> {
+        unsafe { (*self.inner.get()).submit_op(data) }
+    }
+
+    fn poll_op<T: UringOp>(&self, op: &mut Op<T>, cx: &mut Context<'_>) -> Poll<Cqe> {
+        unsafe { poll_op_stage(&mut (*self.inner.get()).ops, op.id, cx) }
+    }
+
+    fn cancel_op(&self, id: u64) -> io::Result<()> {
+        unsafe { (*self.inner.get()).cancel_op(id) }
+    }
+
+    fn detach_op(&self, id: u64, data: Box<dyn UringOp>) {
+        let inner = unsafe { &mut *self.inner.get() };
+        if detach_op_stage(&mut inner.ops, id, data) {
+            let _ = inner.cancel_op(id);
+        }
+    }
+
+    fn park(&self) -> io::Result<()> {
+        self.submit_and_wait()
+    }
+
+    fn supports(&self, opcode: u8) -> bool {
+        unsafe { (*self.inner.get()).supports(opcode) }
+    }
+}
+
+impl AsRawFd for UringDriver {
+    /// The ring fd, which can be handed to `UringConfig::attach_wq` of another driver.
+    fn as_raw_fd(&self) -> RawFd {
+        unsafe { (*self.inner.get()).uring.as_raw_fd() }
+    }
+}
+
+/// The faults injected into an op in flight.
+struct Rewrite {
+    injected: Injected,
+    creates_fd: bool,
+    shortened: Option<Shortened>,
+}
+
+/// Apply the faults injected into op `id` to its CQE, `None` if it is held back.
+fn rewrite_cqe(
+    rewrites: &mut HashMap<u64, Rewrite>,
+    delayed: &mut Vec<(Instant, u64, Cqe)>,
+    id: u64,
+    mut cqe: Cqe,
+) -> Option<Cqe> {
+    let Some(rewrite) = rewrites.remove(&id) else {
+        return Some(cqe);
+    };
+    if let Some(shortened) = rewrite.shortened {
+        shortened.finish();
+    }
+    if let Some(errno) = rewrite.injected.error {
+        // The fd the op created is hidden behind the error, so nobody would close it.
+        if rewrite.creates_fd && cqe.result >= 0 {
+            close_raw(cqe.result);
+        }
+        cqe.result = -errno;
     }
+    if !rewrite.injected.delay.is_zero() {
+        delayed.push((Instant::now() + rewrite.injected.delay, id, cqe));
+        return None;
+    }
+    Some(cqe)
 }
 
 struct UringInner {
     ops: HashMap<u64, OpStage>,
-    uring: IoUring,
+    uring: Ring,
     id_generator: IdGenerator,
     waiting: usize,
+    sqpoll: bool,
+    files: Option<FileTable>,
+    // SQEs of an `OpChain` being built, pushed all at once when it is submitted
+    chain: Option<Vec<(u64, io_uring::squeue::Entry)>>,
+    faults: Faults,
+    // faults to apply to the CQEs of these ops
+    rewrites: HashMap<u64, Rewrite>,
+    // CQEs held back by a `Fault::Delay` until they are due
+    delayed: Vec<(Instant, u64, Cqe)>,
+    // opcodes of the running kernel, `None` if it predates IORING_REGISTER_PROBE
+    probe: Option<Probe>,
+    // runs the ops the kernel does not support, created on first use
+    blocking: Option<BlockingPool>,
+    blocking_inflight: usize,
+    // whether a poll on the eventfd of `blocking` is in flight
+    blocking_armed: bool,
 }
 
 impl UringInner {
-    pub fn new() -> io::Result<Self> {
+    pub fn new(config: &UringConfig) -> io::Result<Self> {
+        let uring = config.build()?;
+        let sqpoll = uring.params().is_setup_sqpoll();
+        let mut probe = Probe::new();
+        let probe = uring
+            .submitter()
+            .register_probe(&mut probe)
+            .ok()
+            .map(|_| probe);
         Ok(Self {
             ops: HashMap::new(),
-            uring: IoUring::new(100)?,
+            uring,
             id_generator: IdGenerator::new(),
             waiting: 0,
+            sqpoll,
+            files: None,
+            chain: None,
+            faults: Faults::default(),
+            rewrites: HashMap::new(),
+            delayed: Vec::new(),
+            probe,
+            blocking: None,
+            blocking_inflight: 0,
+            blocking_armed: false,
         })
     }
 
+    fn supports(&self, opcode: u8) -> bool {
+        match &self.probe {
+            Some(probe) => probe.is_supported(opcode),
+            // IORING_REGISTER_PROBE came with 5.6, older kernels stop at CONNECT.
+            None => opcode <= io_uring::opcode::Connect::CODE,
+        }
+    }
+
+    fn register_files_sparse(&mut self, nr: u32) -> io::Result<()> {
+        self.uring.submitter().register_files_sparse(nr)?;
+        self.files = Some(FileTable::new(nr));
+        Ok(())
+    }
+
+    fn unregister_files(&mut self) -> io::Result<()> {
+        self.uring.submitter().unregister_files()?;
+        self.files = None;
+        Ok(())
+    }
+
     fn submit_sync(&mut self) -> io::Result<()> {
+        // With SQPOLL this only wakes up the poller thread if it went to sleep.
         self.uring.submit()?;
+        // The poller consumes the SQ asynchronously, so it may still be full here.
+        if self.sqpoll && self.uring.submission().is_full() {
+            self.uring.submitter().squeue_wait()?;
+        }
         Ok(())
     }
 
     fn complete_sync(&mut self) -> io::Result<()> {
-        let cq = self.uring.completion();
-        for cqe in cq {
-            let id = cqe.user_data();
-            let result = cqe.result();
-            self.waiting -= 1;
+        let mut blocking = false;
+        self.uring.drain(|id, cqe| {
+            if id == BLOCKING_ID {
+                self.waiting -= 1;
+                blocking = true;
+                return;
+            }
+            let Some(cqe) = rewrite_cqe(&mut self.rewrites, &mut self.delayed, id, cqe) else {
+                return;
+            };
 
-            if let Some(op_stage) = self.ops.get_mut(&id) {
-                match op_stage {
-                    OpStage::Submitted => {
-                        *op_stage = OpStage::Completed(result);
-                    }
-                    OpStage::Waiting(waker) => {
-                        // This is ok because our runtime is single thread
-                        waker.wake_by_ref();
-                        *op_stage = OpStage::Completed(result);
-                    }
-                    OpStage::Completed(_) => unsafe {
-                        std::hint::unreachable_unchecked();
-                    },
-                }
+            // A multishot op stays in flight until a CQE without IORING_CQE_F_MORE.
+            if !io_uring::cqueue::more(cqe.flags) {
+                self.waiting -= 1;
+            }
+            complete_op(&mut self.ops, id, cqe);
+        });
+        if blocking {
+            self.blocking_armed = false;
+            self.complete_blocking()?;
+        }
+        Ok(())
+    }
+
+    /// Deliver the results of the blocking pool, and keep polling its eventfd while
+    /// it still runs ops.
+    fn complete_blocking(&mut self) -> io::Result<()> {
+        let Some(blocking) = &self.blocking else {
+            return Ok(());
+        };
+        for (id, result) in blocking.take_completed() {
+            self.blocking_inflight -= 1;
+            let cqe = Cqe::new(result, 0);
+            if let Some(cqe) = rewrite_cqe(&mut self.rewrites, &mut self.delayed, id, cqe) {
+                self.waiting -= 1;
+                complete_op(&mut self.ops, id, cqe);
             }
         }
+        if self.blocking_inflight > 0 {
+            self.arm_blocking()?;
+        }
+        Ok(())
+    }
+
+    fn arm_blocking(&mut self) -> io::Result<()> {
+        if self.blocking_armed {
+            return Ok(());
+        }
+        let Some(blocking) = &self.blocking else {
+            return Ok(());
+        };
+        let sqe = io_uring::opcode::PollAdd::new(
+            io_uring::types::Fd(blocking.as_raw_fd()),
+            libc::POLLIN as u32,
+        )
+        .build()
+        .user_data(BLOCKING_ID);
+        self.push_sqe(&sqe)?;
+        self.blocking_armed = true;
+        Ok(())
+    }
+
+    /// Run an op the kernel does not support on the blocking pool instead.
+    fn submit_blocking(&mut self, id: u64, sqe: Sqe) -> io::Result<()> {
+        if self.blocking.is_none() {
+            self.blocking = Some(BlockingPool::new(BLOCKING_THREADS)?);
+        }
+        self.arm_blocking()?;
+        self.blocking.as_ref().unwrap().spawn(vec![(id, sqe)]);
+        self.blocking_inflight += 1;
+        self.waiting += 1;
+        Ok(())
+    }
+
+    /// Whether an op has to go to the blocking pool. Fixed files only exist in the
+    /// ring and links only within it, so such ops are left to the kernel to reject.
+    fn needs_blocking(&self, sqe: &Sqe) -> bool {
+        self.chain.is_none()
+            && sqe.flags & io_uring::squeue::Flags::FIXED_FILE.bits() == 0
+            && !self.supports(sqe.opcode)
+            && Sqe::performable(sqe.opcode)
+    }
+
+    /// Deliver the held back completions that are due.
+    fn complete_delayed(&mut self) {
+        let now = Instant::now();
+        let (due, held) = std::mem::take(&mut self.delayed)
+            .into_iter()
+            .partition(|&(due, ..)| due <= now);
+        self.delayed = held;
+        for (_, id, cqe) in due {
+            self.waiting -= 1;
+            complete_op(&mut self.ops, id, cqe);
+        }
+    }
+
+    /// Apply the fault rules to the SQE of op `id`. The op is performed, cut short
+    /// if a rule says so, and the rest is applied to its CQE by `rewrite_cqe`.
+    fn inject(&mut self, id: u64, sqe: io_uring::squeue::Entry) -> io_uring::squeue::Entry {
+        let mut raw = Sqe::new(sqe);
+        let injected = self.faults.inject(&raw);
+        let shortened = injected.short.and_then(|short| raw.shorten(short));
+        if injected.error.is_some() || !injected.delay.is_zero() || shortened.is_some() {
+            let rewrite = Rewrite {
+                injected,
+                creates_fd: raw.creates_fd(),
+                shortened,
+            };
+            self.rewrites.insert(id, rewrite);
+        }
+        raw.into_entry()
+    }
+
+    fn push_sqe(&mut self, sqe: &io_uring::squeue::Entry) -> io::Result<()> {
+        if self.uring.submission().is_full() {
+            self.submit_sync()?;
+        }
+
+        self.waiting += 1;
+        unsafe {
+            self.uring.submission().push(sqe).unwrap();
+        }
         Ok(())
     }
 
     // This is not a real submit like in io_uring
-    fn submit_op<T: UringOp>(&mut self, data: T) -> io::Result<Op<T>> {
+    fn submit_op<T: UringOp>(&mut self, data: T) -> Op<T> {
         let id = self.id_generator.gen_id();
         let mut op = Op::new(id, data);
 
-        if self.uring.submission().is_full() {
-            self.submit_sync()?;
+        let mut sqe = op.build_sqe();
+        if !self.faults.is_empty() {
+            sqe = self.inject(id, sqe);
         }
+        let raw = Sqe::new(sqe.clone());
+        let submitted = if self.needs_blocking(&raw) {
+            self.submit_blocking(id, raw)
+        } else {
+            match self.chain {
+                Some(ref mut chain) => {
+                    chain.push((id, sqe));
+                    Ok(())
+                }
+                None => self.push_sqe(&sqe),
+            }
+        };
+        let stage = match submitted {
+            Ok(()) => OpStage::Submitted,
+            Err(e) => {
+                self.rewrites.remove(&id);
+                let errno = e.raw_os_error().unwrap_or(libc::EIO);
+                OpStage::Completed(Cqe::new(-errno, 0))
+            }
+        };
+        self.ops.insert(id, stage);
+        op
+    }
 
-        self.ops.insert(id, OpStage::Submitted);
+    fn begin_chain(&mut self) -> io::Result<()> {
+        if self.chain.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "op chains cannot be nested",
+            ));
+        }
+        self.chain = Some(Vec::new());
+        Ok(())
+    }
 
-        self.waiting += 1;
-        let sqe = op.build_sqe();
+    fn end_chain(&mut self, hard: bool, commit: bool) -> io::Result<()> {
+        let chain = self.chain.take().unwrap_or_default();
+        let len = chain.len();
+        if !commit || len > self.uring.submission().capacity() {
+            for (id, _) in chain {
+                self.ops.remove(&id);
+            }
+            if commit {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "op chain is longer than the submission queue",
+                ));
+            }
+            return Ok(());
+        }
+
+        // The chain must land in the SQ as a whole, make room for it first.
+        loop {
+            let sq = self.uring.submission();
+            if sq.capacity() - sq.len() >= len {
+                break;
+            }
+            drop(sq);
+            self.uring.submit()?;
+            if self.sqpoll {
+                self.uring.submitter().squeue_wait()?;
+            }
+        }
+
+        let flag = if hard {
+            io_uring::squeue::Flags::IO_HARDLINK
+        } else {
+            io_uring::squeue::Flags::IO_LINK
+        };
+        let sqes: Vec<_> = chain
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, sqe))| if i + 1 < len { sqe.flags(flag) } else { sqe })
+            .collect();
+        self.waiting += len;
         unsafe {
-            self.uring.submission().push(&sqe).unwrap();
+            self.uring.submission().push_multiple(&sqes).unwrap();
         }
+        Ok(())
+    }
+
+    fn submit_multishot_op<T: UringOp + 'static>(&mut self, data: T) -> io::Result<MultishotOp<T>> {
+        let id = self.id_generator.gen_id();
+        let mut op = MultishotOp::new(id, data);
+        self.rearm_multishot_op(&mut op)?;
         Ok(op)
     }
 
-    fn submit_and_wait(&mut self) -> io::Result<()> {
-        if self.waiting > 0 {
-            self.uring.submit_and_wait(1)?;
-            self.complete_sync()?;
+    fn rearm_multishot_op<T: UringOp + 'static>(
+        &mut self,
+        op: &mut MultishotOp<T>,
+    ) -> io::Result<()> {
+        if self.ops.contains_key(&op.id) {
+            return Ok(());
         }
+        let sqe = op.build_sqe();
+        self.push_sqe(&sqe)?;
+        self.ops.insert(
+            op.id,
+            OpStage::Multishot {
+                completions: VecDeque::new(),
+                waker: None,
+                terminated: false,
+            },
+        );
         Ok(())
     }
 
-    fn poll_op<T: UringOp>(
+    fn poll_multishot_op<T: UringOp + 'static>(
         &mut self,
-        op: &mut Op<T>,
+        op: &mut MultishotOp<T>,
         cx: &mut Context<'_>,
-    ) -> Poll<io::Result<i32>> {
+    ) -> Poll<Option<(io::Result<i32>, u32)>> {
         let id = op.id;
-        match self.ops.get_mut(&id) {
-            Some(op_stage) => match op_stage {
-                OpStage::Submitted => {
-                    *op_stage = OpStage::Waiting(cx.waker().clone());
-                    return Poll::Pending;
-                }
-                OpStage::Waiting(waker) => {
-                    if !waker.will_wake(cx.waker()) {
-                        *waker = cx.waker().clone();
+        let Some(OpStage::Multishot {
+            completions,
+            waker,
+            terminated,
+        }) = self.ops.get_mut(&id)
+        else {
+            return Poll::Ready(None);
+        };
+
+        if let Some((result, flags)) = completions.pop_front() {
+            let result = if result < 0 {
+                Err(io::Error::from_raw_os_error(-result))
+            } else {
+                Ok(result)
+            };
+            return Poll::Ready(Some((result, flags)));
+        }
+
+        if *terminated {
+            self.ops.remove(&id);
+            return Poll::Ready(None);
+        }
+
+        match waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => *waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+
+    fn detach_multishot_op(&mut self, id: u64, mut data: Box<dyn UringOp>) {
+        if let Some(OpStage::Multishot {
+            completions,
+            terminated,
+            ..
+        }) = self.ops.remove(&id)
+        {
+            for (result, flags) in completions {
+                data.discard(result, flags);
+            }
+            if !terminated {
+                self.ops.insert(id, OpStage::Detached(data));
+                let _ = self.cancel_op(id);
+            }
+        }
+    }
+
+    fn cancel_op(&mut self, id: u64) -> io::Result<()> {
+        // The cancel completion itself is not tracked.
+        let cancel_id = self.id_generator.gen_id();
+        let sqe = io_uring::opcode::AsyncCancel::new(id)
+            .build()
+            .user_data(cancel_id);
+        self.push_sqe(&sqe)
+    }
+
+    fn submit_and_wait(&mut self) -> io::Result<()> {
+        if self.waiting == 0 {
+            return Ok(());
+        }
+
+        // Held back completions bound how long we may block.
+        if let Some(due) = self.delayed.iter().map(|&(due, ..)| due).min() {
+            let timeout = due.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                self.uring.submit()?;
+            } else {
+                let timespec = io_uring::types::Timespec::from(timeout);
+                let args = io_uring::types::SubmitArgs::new().timespec(&timespec);
+                match self.uring.submitter().submit_with_args(1, &args) {
+                    Err(e) if e.raw_os_error() == Some(libc::ETIME) => {}
+                    res => {
+                        res?;
                     }
-                    return Poll::Pending;
-                }
-                _ => {}
-            },
-            None => panic!(),
-        }
-
-        match self.ops.remove(&id) {
-            Some(op_stage) => match op_stage {
-                OpStage::Completed(result) => {
-                    let result = if result < 0 {
-                        Err(io::Error::from_raw_os_error(-result))
-                    } else {
-                        Ok(result)
-                    };
-                    Poll::Ready(result)
-                }
-                _ => {
-                    unreachable!("unexpected stage!")
                 }
-            },
-            None => panic!(),
+            }
+            self.complete_sync()?;
+            self.complete_delayed();
+            return Ok(());
+        }
+
+        if self.sqpoll {
+            // The poller thread picks up SQEs and posts CQEs on its own, so we only
+            // have to enter the kernel to block when there is nothing to reap yet.
+            // Pending SQEs are never seen by a sleeping poller, which has to be woken
+            // up with IORING_ENTER_SQ_WAKEUP (the io_uring crate sets it whenever
+            // IORING_SQ_NEED_WAKEUP is raised).
+            let pending = {
+                let mut sq = self.uring.submission();
+                sq.sync();
+                !sq.is_empty()
+            };
+            if self.uring.completion_is_empty() {
+                self.uring.submit_and_wait(1)?;
+            } else if pending && self.uring.submission().need_wakeup() {
+                self.uring.submit()?;
+            }
+        } else {
+            self.uring.submit_and_wait(1)?;
+        }
+        self.complete_sync()
+    }
+
+    /// Cancel every op in flight and wait until neither the kernel nor the blocking
+    /// pool uses their buffers anymore.
+    fn cancel_all(&mut self) -> io::Result<()> {
+        let in_flight: Vec<u64> = self
+            .ops
+            .iter()
+            .filter(|(_, stage)| {
+                !matches!(
+                    stage,
+                    OpStage::Completed(_)
+                        | OpStage::Multishot {
+                            terminated: true,
+                            ..
+                        }
+                )
+            })
+            .map(|(&id, _)| id)
+            .collect();
+        for id in in_flight {
+            self.cancel_op(id)?;
         }
+        // Held back completions are in `waiting` too, but their ops are done.
+        while self.waiting > self.delayed.len() {
+            match self.uring.submit_and_wait(1) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                res => res?,
+            };
+            self.complete_sync()?;
+        }
+        for (_, id, cqe) in std::mem::take(&mut self.delayed) {
+            self.waiting -= 1;
+            complete_op(&mut self.ops, id, cqe);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UringInner {
+    fn drop(&mut self) {
+        // Freeing `ops`, or a task only a waker in it keeps alive, frees the buffers
+        // of the ops in flight, so the kernel has to let go of them first. The
+        // wakers are set aside meanwhile, so that no task is woken.
+        let wakers: Vec<Waker> = self
+            .ops
+            .values_mut()
+            .filter_map(|stage| match stage {
+                OpStage::Waiting(_) => match std::mem::replace(stage, OpStage::Submitted) {
+                    OpStage::Waiting(waker) => Some(waker),
+                    _ => unreachable!(),
+                },
+                OpStage::Multishot { waker, .. } => waker.take(),
+                _ => None,
+            })
+            .collect();
+        if self.cancel_all().is_err() {
+            // Leak what the kernel may still write into rather than free it under it.
+            std::mem::forget(wakers);
+            std::mem::forget(std::mem::take(&mut self.ops));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::fd::{FromRawFd, IntoRawFd};
+    use std::os::unix::net::UnixStream;
+    use std::pin::{Pin, pin};
+
+    use crate::driver::op::Op;
+    use crate::runtime::{DriverKind, Runtime};
+
+    fn runtime() -> Runtime {
+        Runtime::builder()
+            .driver(DriverKind::Uring)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn drop_runtime_with_pending_read() {
+        let (ours, mut peer) = UnixStream::pair().unwrap();
+        let fd = ours.into_raw_fd();
+        let runtime = runtime();
+        let mut held = None;
+        runtime.block_on(async {
+            let mut op = Op::recv(fd, Vec::with_capacity(64));
+            std::future::poll_fn(|cx| {
+                assert!(Pin::new(&mut op).poll(cx).is_pending());
+                std::task::Poll::Ready(())
+            })
+            .await;
+            held = Some(op);
+        });
+        // The read is canceled before the ring goes away, so nothing fills its
+        // buffer anymore, which is freed with the op.
+        drop(runtime);
+        peer.write_all(b"late").unwrap();
+        drop(held);
+
+        let mut ours = unsafe { UnixStream::from_raw_fd(fd) };
+        let mut buf = [0; 4];
+        ours.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"late");
+    }
+
+    #[test]
+    fn drop_runtime_with_detached_read() {
+        let (ours, mut peer) = UnixStream::pair().unwrap();
+        let fd = ours.into_raw_fd();
+        let runtime = runtime();
+        runtime.block_on(async {
+            let mut op = pin!(Op::recv(fd, Vec::with_capacity(64)));
+            // Submit it, then drop it while in flight.
+            std::future::poll_fn(|cx| {
+                assert!(op.as_mut().poll(cx).is_pending());
+                std::task::Poll::Ready(())
+            })
+            .await;
+        });
+        drop(runtime);
+
+        peer.write_all(b"late").unwrap();
+        let mut ours = unsafe { UnixStream::from_raw_fd(fd) };
+        let mut buf = [0; 4];
+        ours.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"late");
     }
 }
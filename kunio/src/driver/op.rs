@@ -1,20 +1,68 @@
+use std::collections::VecDeque;
 use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
 
+use crate::driver::Driver;
 use crate::runtime::RUNTIME;
 
 mod accept;
+mod accept_multi;
 mod close;
 mod connect;
+mod fallocate;
+mod fsync;
+mod ftruncate;
+mod msg;
 mod open;
 mod read;
+mod readv;
 mod recv;
+mod recv_multi;
 mod send;
 mod socket;
+mod statx;
+mod sync_range;
 mod write;
+mod writev;
 
-pub struct Op<T> {
+pub use accept_multi::AcceptMulti;
+pub use recv_multi::RecvMulti;
+
+/// Most bytes the kernel moves in one read or write (`MAX_RW_COUNT`), which also
+/// keeps the result within the `i32` of a CQE.
+pub(crate) const MAX_RW_COUNT: usize = i32::MAX as usize & !4095;
+
+/// Fail with `InvalidInput` if a transfer of `len` bytes does not fit in one op.
+pub(crate) fn check_rw_len(len: usize) -> io::Result<()> {
+    if len > MAX_RW_COUNT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "buffer is larger than a single read or write can move",
+        ));
+    }
+    Ok(())
+}
+
+/// Like `check_rw_len`, for the total length of a vectored transfer.
+pub(crate) fn check_iovecs_len(iovecs: &[libc::iovec]) -> io::Result<()> {
+    let total = iovecs
+        .iter()
+        .fold(0usize, |total, iov| total.saturating_add(iov.iov_len));
+    check_rw_len(total)
+}
+
+/// The SQE length of a transfer of `len` bytes, which the callers checked with
+/// `check_rw_len`. It is only clamped so that the cast cannot wrap.
+pub(crate) fn rw_len(len: usize) -> u32 {
+    len.min(MAX_RW_COUNT) as u32
+}
+
+/// An op in flight, completing with the data it was submitted with.
+///
+/// Dropping it before completion hands the data over to the driver, which cancels
+/// the op and keeps the data alive until the kernel is done with it.
+pub struct Op<T: UringOp> {
     pub id: u64,
     data: Option<T>,
 }
@@ -22,16 +70,73 @@ pub struct Op<T> {
 pub struct Completion<T> {
     pub data: T,
     pub result: io::Result<i32>,
+    /// `IORING_CQE_F_*` flags of the CQE.
+    pub flags: u32,
+    /// Extra payload of a big CQE, see `UringConfig::big_cqe`. Zero otherwise.
+    pub big_cqe: [u64; 2],
+}
+
+/// The CQE of an op as the driver reports it.
+#[derive(Clone, Copy, Debug)]
+pub struct Cqe {
+    pub result: i32,
+    pub flags: u32,
+    pub big_cqe: [u64; 2],
+}
+
+impl Cqe {
+    pub fn new(result: i32, flags: u32) -> Self {
+        Self {
+            result,
+            flags,
+            big_cqe: [0; 2],
+        }
+    }
+}
+
+impl<T> Completion<T> {
+    /// Whether the op never ran because an earlier op of its `OpChain` failed.
+    pub fn is_canceled(&self) -> bool {
+        matches!(&self.result, Err(e) if e.raw_os_error() == Some(libc::ECANCELED))
+    }
 }
 
 pub enum OpStage {
     Submitted,
     Waiting(Waker),
-    Completed(i32),
+    Completed(Cqe),
+    /// A multishot op, whose completions are queued until polled. It is terminated
+    /// once a CQE without `IORING_CQE_F_MORE` arrives.
+    Multishot {
+        completions: VecDeque<(i32, u32)>,
+        waker: Option<Waker>,
+        terminated: bool,
+    },
+    /// An op nobody waits for anymore; its completions are discarded.
+    Detached(Box<dyn UringOp>),
 }
 
-pub trait UringOp {
+/// An io_uring operation. Implement it to issue opcodes kunio has no op for, and
+/// submit it with `Op::submit`.
+///
+/// Everything the SQE points to (buffers, paths, sockaddrs, ...) must be owned by
+/// the op and live on the heap: the op is moved after `build_sqe`, and the kernel
+/// may use those addresses until the op completes, even if the `Op` is dropped.
+pub trait UringOp: 'static {
+    /// Build the SQE. The driver sets its `user_data`, and the link flags if the op
+    /// is part of an `OpChain`.
     fn build_sqe(&mut self) -> io_uring::squeue::Entry;
+
+    /// Release what a completion nobody is going to observe carries: a new fd
+    /// nobody is going to own is closed with `close_raw`, a picked ring buffer is
+    /// given back, and so on.
+    fn discard(&mut self, _result: i32, _flags: u32) {}
+}
+
+/// An op producing a completion for every CQE until the kernel terminates it.
+pub struct MultishotOp<T: UringOp + 'static> {
+    pub id: u64,
+    data: Option<T>,
 }
 
 impl<T: UringOp> Op<T> {
@@ -42,6 +147,17 @@ impl<T: UringOp> Op<T> {
         }
     }
 
+    /// Submit `data` to the driver of the current runtime.
+    pub fn submit(data: T) -> Op<T> {
+        RUNTIME.with(|runtime| runtime.driver.submit_op(data))
+    }
+
+    /// Ask the kernel to cancel the op (`IORING_OP_ASYNC_CANCEL`). It still has to
+    /// be awaited, and completes with `ECANCELED` if the cancellation won.
+    pub fn cancel(&self) -> io::Result<()> {
+        RUNTIME.with(|runtime| runtime.driver.cancel_op(self.id))
+    }
+
     pub fn build_sqe(&mut self) -> io_uring::squeue::Entry {
         match self.data {
             Some(ref mut data) => data.build_sqe().user_data(self.id),
@@ -49,11 +165,21 @@ impl<T: UringOp> Op<T> {
         }
     }
 
-    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<i32>> {
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Cqe> {
         RUNTIME.with(|runtime| runtime.driver.poll_op(self, cx))
     }
 }
 
+impl<T: UringOp> Drop for Op<T> {
+    fn drop(&mut self) {
+        if let Some(data) = self.data.take()
+            && RUNTIME.is_set()
+        {
+            RUNTIME.with(|runtime| runtime.driver.detach_op(self.id, Box::new(data)));
+        }
+    }
+}
+
 impl<T: UringOp> Future for Op<T> {
     type Output = Completion<T>;
 
@@ -63,10 +189,70 @@ impl<T: UringOp> Future for Op<T> {
 
         match op.poll(cx) {
             Poll::Pending => Poll::Pending,
-            Poll::Ready(result) => {
+            Poll::Ready(cqe) => {
                 let data = op.data.take().unwrap();
-                Poll::Ready(Completion { data, result })
+                let result = if cqe.result < 0 {
+                    Err(io::Error::from_raw_os_error(-cqe.result))
+                } else {
+                    Ok(cqe.result)
+                };
+                Poll::Ready(Completion {
+                    data,
+                    result,
+                    flags: cqe.flags,
+                    big_cqe: cqe.big_cqe,
+                })
             }
         }
     }
 }
+
+impl<T: UringOp + 'static> MultishotOp<T> {
+    pub fn new(id: u64, data: T) -> Self {
+        Self {
+            id,
+            data: Some(data),
+        }
+    }
+
+    pub fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        match self.data {
+            Some(ref mut data) => data.build_sqe().user_data(self.id),
+            None => unsafe { std::hint::unreachable_unchecked() },
+        }
+    }
+
+    pub fn data(&self) -> &T {
+        self.data.as_ref().unwrap()
+    }
+
+    /// The next completion, or `None` once the kernel terminated the op.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<(io::Result<i32>, u32)>> {
+        RUNTIME.with(|runtime| {
+            runtime
+                .driver
+                .uring()
+                .expect("multishot ops only exist on the io_uring driver")
+                .poll_multishot_op(self, cx)
+        })
+    }
+
+    /// Submit the op again after it was terminated.
+    pub fn rearm(&mut self) -> io::Result<()> {
+        RUNTIME.with(|runtime| runtime.driver.uring()?.rearm_multishot_op(self))
+    }
+}
+
+impl<T: UringOp + 'static> Drop for MultishotOp<T> {
+    fn drop(&mut self) {
+        if let Some(data) = self.data.take()
+            && RUNTIME.is_set()
+        {
+            RUNTIME.with(|runtime| {
+                if let Ok(driver) = runtime.driver.uring() {
+                    driver.detach_multishot_op(self.id, Box::new(data));
+                }
+            });
+        }
+    }
+}
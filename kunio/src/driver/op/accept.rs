@@ -1,37 +1,71 @@
-use std::io;
 use std::mem::MaybeUninit;
-use std::os::fd::RawFd;
 
 use super::Op;
 use super::UringOp;
+use crate::driver::Driver;
 
 use io_uring::{opcode, types};
 
+use crate::driver::fd::{FixedFd, UringFd, close_raw, with_fd};
+
 pub struct Accept {
-    fd: RawFd,
+    fd: UringFd,
     pub addr: Box<(
         MaybeUninit<libc::sockaddr_storage>,
         MaybeUninit<libc::socklen_t>,
     )>,
+    pub file_index: Option<FixedFd>,
 }
 
 impl UringOp for Accept {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Accept::new(
-            types::Fd(self.fd),
+        let file_index = self
+            .file_index
+            .map(|fixed| types::DestinationSlot::try_from_slot_target(fixed.slot()).unwrap());
+        // The kernel refuses SOCK_CLOEXEC for a direct descriptor, which has no fd to
+        // leak into a child anyway.
+        let flags = if file_index.is_none() {
+            libc::SOCK_CLOEXEC
+        } else {
+            0
+        };
+        with_fd!(self.fd, |fd| opcode::Accept::new(
+            fd,
             self.addr.0.as_mut_ptr() as *mut _,
             self.addr.1.as_mut_ptr() as *mut _,
         )
-        .build()
+        .flags(flags)
+        .file_index(file_index)
+        .build())
+    }
+
+    fn discard(&mut self, result: i32, _flags: u32) {
+        if result >= 0 && self.file_index.is_none() {
+            close_raw(result);
+        }
     }
 }
 
 impl Op<Accept> {
-    pub fn accept(fd: RawFd) -> io::Result<Op<Accept>> {
+    pub fn accept(fd: impl Into<UringFd>) -> Op<Accept> {
+        Self::accept_inner(fd.into(), None)
+    }
+
+    /// Accept a connection straight into the registered file table slot `file_index`
+    /// (a direct descriptor). The result is 0 on success.
+    pub fn accept_fixed(fd: impl Into<UringFd>, file_index: FixedFd) -> Op<Accept> {
+        Self::accept_inner(fd.into(), Some(file_index))
+    }
+
+    fn accept_inner(fd: UringFd, file_index: Option<FixedFd>) -> Op<Accept> {
         crate::runtime::RUNTIME.with(|runtime| {
+            let mut addr = Box::new((MaybeUninit::uninit(), MaybeUninit::uninit()));
+            addr.1
+                .write(std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t);
             runtime.driver.submit_op(Accept {
                 fd,
-                addr: Box::new((MaybeUninit::uninit(), MaybeUninit::uninit())),
+                addr,
+                file_index,
             })
         })
     }
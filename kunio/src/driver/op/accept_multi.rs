@@ -0,0 +1,39 @@
+use std::io;
+
+use super::MultishotOp;
+use super::UringOp;
+
+use io_uring::opcode;
+
+use crate::driver::fd::{UringFd, close_raw, with_fd};
+
+pub struct AcceptMulti {
+    fd: UringFd,
+}
+
+impl UringOp for AcceptMulti {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::AcceptMulti::new(fd)
+            .flags(libc::SOCK_CLOEXEC)
+            .build())
+    }
+
+    fn discard(&mut self, result: i32, _flags: u32) {
+        if result >= 0 {
+            close_raw(result);
+        }
+    }
+}
+
+impl MultishotOp<AcceptMulti> {
+    /// Keep accepting connections on `fd` with a single `IORING_ACCEPT_MULTISHOT` SQE.
+    pub fn accept_multi(fd: impl Into<UringFd>) -> io::Result<MultishotOp<AcceptMulti>> {
+        let fd = fd.into();
+        crate::runtime::RUNTIME.with(|runtime| {
+            runtime
+                .driver
+                .uring()?
+                .submit_multishot_op(AcceptMulti { fd })
+        })
+    }
+}
@@ -1,24 +1,24 @@
-use std::io;
-use std::os::fd::RawFd;
-
 use super::Op;
 use super::UringOp;
+use crate::driver::Driver;
 
+use crate::driver::fd::{UringFd, with_fd};
 use crate::runtime::RUNTIME;
-use io_uring::{opcode, types};
+use io_uring::opcode;
 
 pub struct Close {
-    fd: RawFd,
+    fd: UringFd,
 }
 
 impl UringOp for Close {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Close::new(types::Fd(self.fd)).build()
+        with_fd!(self.fd, |fd| opcode::Close::new(fd).build())
     }
 }
 
 impl Op<Close> {
-    pub fn close(fd: RawFd) -> io::Result<Op<Close>> {
+    pub fn close(fd: impl Into<UringFd>) -> Op<Close> {
+        let fd = fd.into();
         RUNTIME.with(|runtime| runtime.driver.submit_op(Close { fd }))
     }
 }
@@ -1,26 +1,33 @@
-use std::io;
 use std::net::SocketAddr;
-use std::os::fd::RawFd;
 
 use super::Op;
 use super::UringOp;
+use crate::driver::Driver;
 
-use io_uring::{opcode, types};
+use io_uring::opcode;
+
+use crate::driver::fd::{UringFd, with_fd};
 
 pub struct Connect {
-    fd: RawFd,
+    fd: UringFd,
     addr: Box<SocketAddrCRepr>, // Boxed ! async reason
     addrlen: libc::socklen_t,
 }
 
 impl UringOp for Connect {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Connect::new(types::Fd(self.fd), self.addr.as_ptr(), self.addrlen).build()
+        with_fd!(self.fd, |fd| opcode::Connect::new(
+            fd,
+            self.addr.as_ptr(),
+            self.addrlen
+        )
+        .build())
     }
 }
 
 impl Op<Connect> {
-    pub fn connect(fd: RawFd, addr: SocketAddr) -> io::Result<Op<Connect>> {
+    pub fn connect(fd: impl Into<UringFd>, addr: SocketAddr) -> Op<Connect> {
+        let fd = fd.into();
         crate::runtime::RUNTIME.with(|runtime| {
             let (addr, addrlen) = socket_addr(&addr);
             runtime.driver.submit_op(Connect {
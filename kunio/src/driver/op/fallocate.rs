@@ -0,0 +1,40 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::opcode;
+
+use crate::driver::fd::{UringFd, with_fd};
+use crate::runtime::RUNTIME;
+
+pub struct Fallocate {
+    fd: UringFd,
+    offset: u64,
+    len: u64,
+    mode: i32,
+}
+
+impl UringOp for Fallocate {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::Fallocate::new(fd, self.len)
+            .offset(self.offset)
+            .mode(self.mode)
+            .build())
+    }
+}
+
+impl Op<Fallocate> {
+    /// `fallocate(2)` of `len` bytes at `offset`, `mode` being the `FALLOC_FL_*`
+    /// flags.
+    pub fn fallocate(fd: impl Into<UringFd>, offset: u64, len: u64, mode: i32) -> Op<Fallocate> {
+        let fd = fd.into();
+        RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(Fallocate {
+                fd,
+                offset,
+                len,
+                mode,
+            })
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::{opcode, types};
+
+use crate::driver::fd::{UringFd, with_fd};
+use crate::runtime::RUNTIME;
+
+pub struct Fsync {
+    fd: UringFd,
+    flags: types::FsyncFlags,
+}
+
+impl UringOp for Fsync {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::Fsync::new(fd)
+            .flags(self.flags)
+            .build())
+    }
+}
+
+impl Op<Fsync> {
+    /// Flush the file's data and metadata to the device, like `fsync(2)`.
+    pub fn fsync(fd: impl Into<UringFd>) -> Op<Fsync> {
+        Self::fsync_inner(fd.into(), types::FsyncFlags::empty())
+    }
+
+    /// Flush the file's data and only the metadata needed to read it back, like
+    /// `fdatasync(2)`.
+    pub fn datasync(fd: impl Into<UringFd>) -> Op<Fsync> {
+        Self::fsync_inner(fd.into(), types::FsyncFlags::DATASYNC)
+    }
+
+    fn fsync_inner(fd: UringFd, flags: types::FsyncFlags) -> Op<Fsync> {
+        RUNTIME.with(|runtime| runtime.driver.submit_op(Fsync { fd, flags }))
+    }
+}
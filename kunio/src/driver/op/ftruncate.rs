@@ -0,0 +1,28 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::opcode;
+
+use crate::driver::fd::{UringFd, with_fd};
+use crate::runtime::RUNTIME;
+
+pub struct Ftruncate {
+    fd: UringFd,
+    len: u64,
+}
+
+impl UringOp for Ftruncate {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::Ftruncate::new(fd, self.len).build())
+    }
+}
+
+impl Op<Ftruncate> {
+    /// Cut or extend the file to `len` bytes. `IORING_OP_FTRUNCATE` needs Linux 6.9,
+    /// the driver runs it on its blocking pool on older kernels.
+    pub fn ftruncate(fd: impl Into<UringFd>, len: u64) -> Op<Ftruncate> {
+        let fd = fd.into();
+        RUNTIME.with(|runtime| runtime.driver.submit_op(Ftruncate { fd, len }))
+    }
+}
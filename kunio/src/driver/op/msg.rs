@@ -0,0 +1,89 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::opcode;
+
+use crate::buf::{IoVecBuf, IoVecBufMut};
+use crate::driver::fd::{UringFd, with_fd};
+
+/// A `sendmsg` of several buffers, written out in order.
+pub struct SendMsg<T> {
+    fd: UringFd,
+    pub bufs: T,
+    iovecs: Vec<libc::iovec>,
+    msg: Box<libc::msghdr>,
+    flags: u32,
+}
+
+impl<T: IoVecBuf> UringOp for SendMsg<T> {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        self.iovecs = self.bufs.read_iovecs();
+        self.msg.msg_iov = self.iovecs.as_mut_ptr();
+        self.msg.msg_iovlen = self.iovecs.len();
+        with_fd!(self.fd, |fd| opcode::SendMsg::new(fd, &*self.msg)
+            .flags(self.flags)
+            .build())
+    }
+}
+
+impl<T: IoVecBuf> Op<SendMsg<T>> {
+    /// `flags` are the `MSG_*` flags of `sendmsg(2)`.
+    pub fn sendmsg(fd: impl Into<UringFd>, bufs: T, flags: u32) -> Op<SendMsg<T>> {
+        let fd = fd.into();
+        crate::runtime::RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(SendMsg {
+                fd,
+                bufs,
+                iovecs: Vec::new(),
+                // Safety: an all zero msghdr is empty.
+                msg: Box::new(unsafe { std::mem::zeroed() }),
+                flags,
+            })
+        })
+    }
+}
+
+/// A `recvmsg` into several buffers, filled one after the other.
+pub struct RecvMsg<T> {
+    fd: UringFd,
+    pub bufs: T,
+    iovecs: Vec<libc::iovec>,
+    msg: Box<libc::msghdr>,
+    flags: u32,
+}
+
+impl<T> RecvMsg<T> {
+    /// The `MSG_*` flags the kernel reported, e.g. `MSG_TRUNC`.
+    pub fn msg_flags(&self) -> i32 {
+        self.msg.msg_flags
+    }
+}
+
+impl<T: IoVecBufMut> UringOp for RecvMsg<T> {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        self.iovecs = self.bufs.write_iovecs();
+        self.msg.msg_iov = self.iovecs.as_mut_ptr();
+        self.msg.msg_iovlen = self.iovecs.len();
+        with_fd!(self.fd, |fd| opcode::RecvMsg::new(fd, &mut *self.msg)
+            .flags(self.flags)
+            .build())
+    }
+}
+
+impl<T: IoVecBufMut> Op<RecvMsg<T>> {
+    /// `flags` are the `MSG_*` flags of `recvmsg(2)`.
+    pub fn recvmsg(fd: impl Into<UringFd>, bufs: T, flags: u32) -> Op<RecvMsg<T>> {
+        let fd = fd.into();
+        crate::runtime::RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(RecvMsg {
+                fd,
+                bufs,
+                iovecs: Vec::new(),
+                // Safety: an all zero msghdr is empty.
+                msg: Box::new(unsafe { std::mem::zeroed() }),
+                flags,
+            })
+        })
+    }
+}
@@ -5,27 +5,102 @@ use std::path::Path;
 
 use super::Op;
 use super::UringOp;
+use crate::driver::Driver;
 
 use io_uring::{opcode, types};
 
+use crate::driver::fd::{FixedFd, close_raw};
+
 pub struct Open {
     path: CString,
     flags: i32,
     mode: libc::mode_t,
+    // `openat2` arguments, boxed as the kernel reads them after the op moved.
+    how: Option<Box<types::OpenHow>>,
+    file_index: Option<FixedFd>,
 }
 
 impl UringOp for Open {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        let slot = self
+            .file_index
+            .map(|fixed| types::DestinationSlot::try_from_slot_target(fixed.slot()).unwrap());
+        if let Some(how) = &self.how {
+            return opcode::OpenAt2::new(
+                types::Fd(libc::AT_FDCWD),
+                self.path.as_c_str().as_ptr(),
+                &**how,
+            )
+            .file_index(slot)
+            .build();
+        }
         opcode::OpenAt::new(types::Fd(libc::AT_FDCWD), self.path.as_c_str().as_ptr())
             .flags(self.flags)
             .mode(self.mode)
+            .file_index(slot)
             .build()
     }
+
+    fn discard(&mut self, result: i32, _flags: u32) {
+        if result >= 0 && self.file_index.is_none() {
+            close_raw(result);
+        }
+    }
 }
 
 impl Op<Open> {
     pub fn open<P: AsRef<Path>>(path: P, flags: i32, mode: libc::mode_t) -> io::Result<Op<Open>> {
-        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
-        crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(Open { path, flags, mode }))
+        Self::open_inner(path.as_ref(), flags, mode, None, None)
+    }
+
+    /// Open `path` with `openat2(2)`, resolving it as `resolve` (`RESOLVE_*` flags)
+    /// allows.
+    pub fn openat2<P: AsRef<Path>>(
+        path: P,
+        flags: i32,
+        mode: libc::mode_t,
+        resolve: u64,
+    ) -> io::Result<Op<Open>> {
+        // Unlike `openat`, a mode without a file to create is an error.
+        let mode = if flags & (libc::O_CREAT | libc::O_TMPFILE) != 0 {
+            mode
+        } else {
+            0
+        };
+        let how = types::OpenHow::new()
+            .flags(flags as u64)
+            .mode(mode as u64)
+            .resolve(resolve);
+        Self::open_inner(path.as_ref(), flags, mode, Some(Box::new(how)), None)
+    }
+
+    /// Open `path` straight into the registered file table slot `file_index`
+    /// (a direct descriptor). The result is 0 on success.
+    pub fn open_fixed<P: AsRef<Path>>(
+        path: P,
+        flags: i32,
+        mode: libc::mode_t,
+        file_index: FixedFd,
+    ) -> io::Result<Op<Open>> {
+        Self::open_inner(path.as_ref(), flags, mode, None, Some(file_index))
+    }
+
+    fn open_inner(
+        path: &Path,
+        flags: i32,
+        mode: libc::mode_t,
+        how: Option<Box<types::OpenHow>>,
+        file_index: Option<FixedFd>,
+    ) -> io::Result<Op<Open>> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        Ok(crate::runtime::RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(Open {
+                path,
+                flags,
+                mode,
+                how,
+                file_index,
+            })
+        }))
     }
 }
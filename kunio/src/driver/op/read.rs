@@ -1,56 +1,71 @@
-use std::io;
-use std::os::fd::RawFd;
-
 use super::Op;
 use super::UringOp;
+use super::rw_len;
+use crate::driver::Driver;
 
-use io_uring::{opcode, types};
+use io_uring::opcode;
 
 use crate::buf::IoBufMut;
+use crate::driver::fd::{UringFd, with_fd};
 
 pub struct Read<T> {
-    fd: RawFd,
+    fd: UringFd,
     pub buf: T,
 }
 
 impl<T: IoBufMut> UringOp for Read<T> {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Read::new(
-            types::Fd(self.fd),
-            self.buf.write_ptr(),
-            self.buf.available_len(),
-        )
-        .offset(-1i64 as u64)
-        .build()
+        read_sqe(self.fd, &mut self.buf, -1i64 as u64)
     }
 }
 
 impl<T: IoBufMut> Op<Read<T>> {
-    pub fn read(fd: RawFd, buf: T) -> io::Result<Op<Read<T>>> {
+    pub fn read(fd: impl Into<UringFd>, buf: T) -> Op<Read<T>> {
+        let fd = fd.into();
         crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(Read { fd, buf }))
     }
 }
 
 pub struct ReadAt<T> {
-    fd: RawFd,
+    fd: UringFd,
     pub buf: T,
     offset: u64,
 }
 
 impl<T: IoBufMut> UringOp for ReadAt<T> {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Read::new(
-            types::Fd(self.fd),
-            self.buf.write_ptr(),
-            self.buf.available_len(),
-        )
-        .offset(self.offset)
-        .build()
+        read_sqe(self.fd, &mut self.buf, self.offset)
     }
 }
 
 impl<T: IoBufMut> Op<ReadAt<T>> {
-    pub fn read_at(fd: RawFd, buf: T, offset: u64) -> io::Result<Op<ReadAt<T>>> {
+    pub fn read_at(fd: impl Into<UringFd>, buf: T, offset: u64) -> Op<ReadAt<T>> {
+        let fd = fd.into();
         crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(ReadAt { fd, buf, offset }))
     }
 }
+
+/// Build a `Read`, or a `ReadFixed` if `buf` is a registered buffer.
+pub(crate) fn read_sqe<T: IoBufMut>(
+    fd: UringFd,
+    buf: &mut T,
+    offset: u64,
+) -> io_uring::squeue::Entry {
+    let (ptr, len) = spare(buf);
+    match buf.buf_index() {
+        Some(index) => with_fd!(fd, |fd| opcode::ReadFixed::new(fd, ptr, len, index)
+            .offset(offset)
+            .build()),
+        None => with_fd!(fd, |fd| opcode::Read::new(fd, ptr, len)
+            .offset(offset)
+            .build()),
+    }
+}
+
+/// The uninitialized part of `buf`, which reads append to.
+pub(crate) fn spare<T: IoBufMut>(buf: &mut T) -> (*mut u8, u32) {
+    let init = buf.bytes_init();
+    // Safety: `bytes_init` is within the buffer.
+    let ptr = unsafe { buf.stable_mut_ptr().add(init) };
+    (ptr, rw_len(buf.bytes_total() - init))
+}
@@ -0,0 +1,48 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::opcode;
+
+use crate::buf::IoVecBufMut;
+use crate::driver::fd::{UringFd, with_fd};
+
+/// A `readv` into several buffers, filled one after the other.
+pub struct Readv<T> {
+    fd: UringFd,
+    pub bufs: T,
+    // on the heap, so the kernel sees them at a stable address
+    iovecs: Vec<libc::iovec>,
+    offset: u64,
+}
+
+impl<T: IoVecBufMut> UringOp for Readv<T> {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        self.iovecs = self.bufs.write_iovecs();
+        with_fd!(self.fd, |fd| opcode::Readv::new(
+            fd,
+            self.iovecs.as_ptr(),
+            self.iovecs.len() as u32
+        )
+        .offset(self.offset)
+        .build())
+    }
+}
+
+impl<T: IoVecBufMut> Op<Readv<T>> {
+    pub fn readv(fd: impl Into<UringFd>, bufs: T) -> Op<Readv<T>> {
+        Self::readv_at(fd, bufs, -1i64 as u64)
+    }
+
+    pub fn readv_at(fd: impl Into<UringFd>, bufs: T, offset: u64) -> Op<Readv<T>> {
+        let fd = fd.into();
+        crate::runtime::RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(Readv {
+                fd,
+                bufs,
+                iovecs: Vec::new(),
+                offset,
+            })
+        })
+    }
+}
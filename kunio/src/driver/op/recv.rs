@@ -1,31 +1,67 @@
-use std::io;
-use std::os::fd::RawFd;
-
 use super::Op;
 use super::UringOp;
+use super::read::{read_sqe, spare};
+use crate::driver::Driver;
 
-use io_uring::{opcode, types};
+use io_uring::{cqueue, opcode, squeue};
 
-use crate::buf::IoBufMut;
+use crate::buf::{BufRing, IoBufMut};
+use crate::driver::fd::{UringFd, with_fd};
 
 pub struct Recv<T> {
-    fd: RawFd,
+    fd: UringFd,
     pub buf: T,
 }
 
 impl<T: IoBufMut> UringOp for Recv<T> {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Recv::new(
-            types::Fd(self.fd),
-            self.buf.write_ptr(),
-            self.buf.available_len(),
-        )
-        .build()
+        // There is no fixed-buffer recv; a read on a socket is a recv without flags.
+        if self.buf.buf_index().is_some() {
+            return read_sqe(self.fd, &mut self.buf, -1i64 as u64);
+        }
+        let (ptr, len) = spare(&mut self.buf);
+        with_fd!(self.fd, |fd| opcode::Recv::new(fd, ptr, len).build())
     }
 }
 
 impl<T: IoBufMut> Op<Recv<T>> {
-    pub fn recv(fd: RawFd, buf: T) -> io::Result<Op<Recv<T>>> {
+    pub fn recv(fd: impl Into<UringFd>, buf: T) -> Op<Recv<T>> {
+        let fd = fd.into();
         crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(Recv { fd, buf }))
     }
 }
+
+/// A recv with `IOSQE_BUFFER_SELECT`: the kernel picks a buffer from `ring` once
+/// data arrives. The buffer id is carried in the CQE flags.
+pub struct RecvRing {
+    fd: UringFd,
+    pub ring: BufRing,
+}
+
+impl UringOp for RecvRing {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::Recv::new(
+            fd,
+            std::ptr::null_mut(),
+            self.ring.buf_len() as u32
+        )
+        .buf_group(self.ring.bgid())
+        .build()
+        .flags(squeue::Flags::BUFFER_SELECT))
+    }
+
+    fn discard(&mut self, result: i32, flags: u32) {
+        // Nobody is going to take the picked buffer, give it back to the ring.
+        if let Some(bid) = cqueue::buffer_select(flags) {
+            drop(unsafe { self.ring.get_buf(bid, result.max(0) as usize) });
+        }
+    }
+}
+
+impl Op<RecvRing> {
+    pub fn recv_ring(fd: impl Into<UringFd>, ring: &BufRing) -> Op<RecvRing> {
+        let fd = fd.into();
+        let ring = ring.clone();
+        crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(RecvRing { fd, ring }))
+    }
+}
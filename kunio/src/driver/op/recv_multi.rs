@@ -0,0 +1,46 @@
+use std::io;
+
+use super::MultishotOp;
+use super::UringOp;
+
+use io_uring::{cqueue, opcode};
+
+use crate::buf::BufRing;
+use crate::driver::fd::{UringFd, with_fd};
+
+pub struct RecvMulti {
+    fd: UringFd,
+    pub ring: BufRing,
+}
+
+impl UringOp for RecvMulti {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::RecvMulti::new(fd, self.ring.bgid())
+            .build())
+    }
+
+    fn discard(&mut self, result: i32, flags: u32) {
+        // Hand the picked buffer straight back to the ring.
+        if let Some(bid) = cqueue::buffer_select(flags) {
+            drop(unsafe { self.ring.get_buf(bid, result.max(0) as usize) });
+        }
+    }
+}
+
+impl MultishotOp<RecvMulti> {
+    /// Keep receiving on `fd` into buffers picked from `ring` with a single
+    /// `IORING_RECV_MULTISHOT` SQE.
+    pub fn recv_multi(
+        fd: impl Into<UringFd>,
+        ring: &BufRing,
+    ) -> io::Result<MultishotOp<RecvMulti>> {
+        let fd = fd.into();
+        let ring = ring.clone();
+        crate::runtime::RUNTIME.with(|runtime| {
+            runtime
+                .driver
+                .uring()?
+                .submit_multishot_op(RecvMulti { fd, ring })
+        })
+    }
+}
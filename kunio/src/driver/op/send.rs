@@ -1,31 +1,37 @@
-use std::io;
-use std::os::fd::RawFd;
-
 use super::Op;
 use super::UringOp;
+use super::rw_len;
+use super::write::write_sqe;
+use crate::driver::Driver;
 
-use io_uring::{opcode, types};
+use io_uring::opcode;
 
 use crate::buf::IoBuf;
+use crate::driver::fd::{UringFd, with_fd};
 
 pub struct Send<T> {
-    fd: RawFd,
+    fd: UringFd,
     pub buf: T,
 }
 
 impl<T: IoBuf> UringOp for Send<T> {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Send::new(
-            types::Fd(self.fd),
-            self.buf.read_ptr(),
-            self.buf.valid_len(),
+        // There is no fixed-buffer send; a write on a socket is a send without flags.
+        if self.buf.buf_index().is_some() {
+            return write_sqe(self.fd, &self.buf, -1i64 as u64);
+        }
+        with_fd!(self.fd, |fd| opcode::Send::new(
+            fd,
+            self.buf.stable_ptr(),
+            rw_len(self.buf.bytes_init()),
         )
-        .build()
+        .build())
     }
 }
 
 impl<T: IoBuf> Op<Send<T>> {
-    pub fn send(fd: RawFd, buf: T) -> io::Result<Op<Send<T>>> {
+    pub fn send(fd: impl Into<UringFd>, buf: T) -> Op<Send<T>> {
+        let fd = fd.into();
         crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(Send { fd, buf }))
     }
 }
@@ -1,30 +1,65 @@
-use std::io;
-
 use super::Op;
 use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::{opcode, types};
 
-use io_uring::opcode;
+use crate::driver::fd::{FixedFd, close_raw};
 
-// Important !!! linux kernel 5.19 least
+// Needs linux 5.19, `net` falls back to libc::socket on older kernels.
 pub struct Socket {
     domain: i32,
     socket_type: i32,
     protocol: i32,
+    file_index: Option<FixedFd>,
 }
 
 impl UringOp for Socket {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Socket::new(self.domain, self.socket_type, self.protocol).build()
+        opcode::Socket::new(self.domain, self.socket_type, self.protocol)
+            .file_index(
+                self.file_index.map(|fixed| {
+                    types::DestinationSlot::try_from_slot_target(fixed.slot()).unwrap()
+                }),
+            )
+            .build()
+    }
+
+    fn discard(&mut self, result: i32, _flags: u32) {
+        if result >= 0 && self.file_index.is_none() {
+            close_raw(result);
+        }
     }
 }
 
 impl Op<Socket> {
-    pub fn socket(domain: i32, socket_type: i32, protocol: i32) -> io::Result<Op<Socket>> {
+    pub fn socket(domain: i32, socket_type: i32, protocol: i32) -> Op<Socket> {
+        Self::socket_inner(domain, socket_type, protocol, None)
+    }
+
+    /// Create a socket straight into the registered file table slot `file_index`
+    /// (a direct descriptor). The result is 0 on success.
+    pub fn socket_fixed(
+        domain: i32,
+        socket_type: i32,
+        protocol: i32,
+        file_index: FixedFd,
+    ) -> Op<Socket> {
+        Self::socket_inner(domain, socket_type, protocol, Some(file_index))
+    }
+
+    fn socket_inner(
+        domain: i32,
+        socket_type: i32,
+        protocol: i32,
+        file_index: Option<FixedFd>,
+    ) -> Op<Socket> {
         crate::runtime::RUNTIME.with(|runtime| {
             runtime.driver.submit_op(Socket {
                 domain,
                 socket_type,
                 protocol,
+                file_index,
             })
         })
     }
@@ -0,0 +1,53 @@
+use std::ffi::CString;
+use std::io;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::{opcode, types};
+
+pub struct Statx {
+    dirfd: RawFd,
+    path: CString,
+    flags: i32,
+    mask: u32,
+    // Boxed, the kernel writes to it after the op moved.
+    pub statx: Box<libc::statx>,
+}
+
+impl UringOp for Statx {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        let statx = &mut *self.statx as *mut libc::statx as *mut types::statx;
+        opcode::Statx::new(types::Fd(self.dirfd), self.path.as_ptr(), statx)
+            .flags(self.flags)
+            .mask(self.mask)
+            .build()
+    }
+}
+
+impl Op<Statx> {
+    /// `statx(2)` of `path` relative to `dirfd`. With `AT_EMPTY_PATH` and an empty
+    /// path, of `dirfd` itself, which has to be a regular fd.
+    pub fn statx<P: AsRef<Path>>(
+        dirfd: RawFd,
+        path: P,
+        flags: i32,
+        mask: u32,
+    ) -> io::Result<Op<Statx>> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())?;
+        Ok(crate::runtime::RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(Statx {
+                dirfd,
+                path,
+                flags,
+                mask,
+                // Safety: `statx` is plain data.
+                statx: Box::new(unsafe { std::mem::zeroed() }),
+            })
+        }))
+    }
+}
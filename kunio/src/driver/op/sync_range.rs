@@ -0,0 +1,40 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::opcode;
+
+use crate::driver::fd::{UringFd, with_fd};
+use crate::runtime::RUNTIME;
+
+pub struct SyncRange {
+    fd: UringFd,
+    offset: u64,
+    len: u32,
+    flags: u32,
+}
+
+impl UringOp for SyncRange {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        with_fd!(self.fd, |fd| opcode::SyncFileRange::new(fd, self.len)
+            .offset(self.offset)
+            .flags(self.flags)
+            .build())
+    }
+}
+
+impl Op<SyncRange> {
+    /// `sync_file_range(2)` of `len` bytes at `offset`, a `len` of 0 meaning up to
+    /// the end of the file. `flags` are the `SYNC_FILE_RANGE_*` flags.
+    pub fn sync_range(fd: impl Into<UringFd>, offset: u64, len: u32, flags: u32) -> Op<SyncRange> {
+        let fd = fd.into();
+        RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(SyncRange {
+                fd,
+                offset,
+                len,
+                flags,
+            })
+        })
+    }
+}
@@ -1,57 +1,60 @@
-use std::io;
-use std::os::fd::RawFd;
-
 use super::Op;
 use super::UringOp;
+use super::rw_len;
+use crate::driver::Driver;
 
-use io_uring::{opcode, types};
+use io_uring::opcode;
 
 use crate::buf::IoBuf;
+use crate::driver::fd::{UringFd, with_fd};
 
 pub struct Write<T> {
-    fd: RawFd,
+    fd: UringFd,
     pub buf: T,
 }
 
 impl<T: IoBuf> UringOp for Write<T> {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Write::new(
-            types::Fd(self.fd),
-            self.buf.read_ptr(),
-            self.buf.valid_len(),
-        )
-        .offset(-1i64 as u64)
-        .build()
+        write_sqe(self.fd, &self.buf, -1i64 as u64)
     }
 }
 
 impl<T: IoBuf> Op<Write<T>> {
-    pub fn write(fd: RawFd, buf: T) -> io::Result<Op<Write<T>>> {
+    pub fn write(fd: impl Into<UringFd>, buf: T) -> Op<Write<T>> {
+        let fd = fd.into();
         crate::runtime::RUNTIME.with(|runtime| runtime.driver.submit_op(Write { fd, buf }))
     }
 }
 
 pub struct WriteAt<T> {
-    fd: RawFd,
+    fd: UringFd,
     pub buf: T,
     offset: u64,
 }
 
 impl<T: IoBuf> UringOp for WriteAt<T> {
     fn build_sqe(&mut self) -> io_uring::squeue::Entry {
-        opcode::Write::new(
-            types::Fd(self.fd),
-            self.buf.read_ptr(),
-            self.buf.valid_len(),
-        )
-        .offset(self.offset)
-        .build()
+        write_sqe(self.fd, &self.buf, self.offset)
     }
 }
 
 impl<T: IoBuf> Op<WriteAt<T>> {
-    pub fn write_at(fd: RawFd, buf: T, offset: u64) -> io::Result<Op<WriteAt<T>>> {
+    pub fn write_at(fd: impl Into<UringFd>, buf: T, offset: u64) -> Op<WriteAt<T>> {
+        let fd = fd.into();
         crate::runtime::RUNTIME
             .with(|runtime| runtime.driver.submit_op(WriteAt { fd, buf, offset }))
     }
 }
+
+/// Build a `Write`, or a `WriteFixed` if `buf` is a registered buffer.
+pub(crate) fn write_sqe<T: IoBuf>(fd: UringFd, buf: &T, offset: u64) -> io_uring::squeue::Entry {
+    let (ptr, len) = (buf.stable_ptr(), rw_len(buf.bytes_init()));
+    match buf.buf_index() {
+        Some(index) => with_fd!(fd, |fd| opcode::WriteFixed::new(fd, ptr, len, index)
+            .offset(offset)
+            .build()),
+        None => with_fd!(fd, |fd| opcode::Write::new(fd, ptr, len)
+            .offset(offset)
+            .build()),
+    }
+}
@@ -0,0 +1,48 @@
+use super::Op;
+use super::UringOp;
+use crate::driver::Driver;
+
+use io_uring::opcode;
+
+use crate::buf::IoVecBuf;
+use crate::driver::fd::{UringFd, with_fd};
+
+/// A `writev` of several buffers, written out in order.
+pub struct Writev<T> {
+    fd: UringFd,
+    pub bufs: T,
+    // on the heap, so the kernel sees them at a stable address
+    iovecs: Vec<libc::iovec>,
+    offset: u64,
+}
+
+impl<T: IoVecBuf> UringOp for Writev<T> {
+    fn build_sqe(&mut self) -> io_uring::squeue::Entry {
+        self.iovecs = self.bufs.read_iovecs();
+        with_fd!(self.fd, |fd| opcode::Writev::new(
+            fd,
+            self.iovecs.as_ptr(),
+            self.iovecs.len() as u32
+        )
+        .offset(self.offset)
+        .build())
+    }
+}
+
+impl<T: IoVecBuf> Op<Writev<T>> {
+    pub fn writev(fd: impl Into<UringFd>, bufs: T) -> Op<Writev<T>> {
+        Self::writev_at(fd, bufs, -1i64 as u64)
+    }
+
+    pub fn writev_at(fd: impl Into<UringFd>, bufs: T, offset: u64) -> Op<Writev<T>> {
+        let fd = fd.into();
+        crate::runtime::RUNTIME.with(|runtime| {
+            runtime.driver.submit_op(Writev {
+                fd,
+                bufs,
+                iovecs: Vec::new(),
+                offset,
+            })
+        })
+    }
+}
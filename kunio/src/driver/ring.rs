@@ -0,0 +1,71 @@
+use std::io;
+use std::os::fd::{AsRawFd, RawFd};
+
+use io_uring::{IoUring, Parameters, SubmissionQueue, Submitter, cqueue, squeue};
+
+use super::op::Cqe;
+
+/// An io_uring instance with either regular or big (`IORING_SETUP_CQE32`) CQEs.
+pub(crate) enum Ring {
+    Normal(IoUring),
+    Big(IoUring<squeue::Entry, cqueue::Entry32>),
+}
+
+macro_rules! with_ring {
+    ($ring:expr, |$r:ident| $body:expr) => {
+        match $ring {
+            Ring::Normal($r) => $body,
+            Ring::Big($r) => $body,
+        }
+    };
+}
+
+impl Ring {
+    pub fn submission(&mut self) -> SubmissionQueue<'_, squeue::Entry> {
+        with_ring!(self, |r| r.submission())
+    }
+
+    pub fn submitter(&self) -> Submitter<'_> {
+        with_ring!(self, |r| r.submitter())
+    }
+
+    pub fn params(&self) -> &Parameters {
+        with_ring!(self, |r| r.params())
+    }
+
+    pub fn submit(&self) -> io::Result<usize> {
+        with_ring!(self, |r| r.submit())
+    }
+
+    pub fn submit_and_wait(&self, want: usize) -> io::Result<usize> {
+        with_ring!(self, |r| r.submit_and_wait(want))
+    }
+
+    pub fn completion_is_empty(&mut self) -> bool {
+        with_ring!(self, |r| r.completion().is_empty())
+    }
+
+    /// Pop every CQE there is.
+    pub fn drain(&mut self, mut f: impl FnMut(u64, Cqe)) {
+        match self {
+            Ring::Normal(r) => {
+                for cqe in r.completion() {
+                    f(cqe.user_data(), Cqe::new(cqe.result(), cqe.flags()));
+                }
+            }
+            Ring::Big(r) => {
+                for cqe in r.completion() {
+                    let mut big = Cqe::new(cqe.result(), cqe.flags());
+                    big.big_cqe = *cqe.big_cqe();
+                    f(cqe.user_data(), big);
+                }
+            }
+        }
+    }
+}
+
+impl AsRawFd for Ring {
+    fn as_raw_fd(&self) -> RawFd {
+        with_ring!(self, |r| r.as_raw_fd())
+    }
+}
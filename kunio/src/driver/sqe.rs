@@ -0,0 +1,331 @@
+use std::io;
+
+use io_uring::{opcode, squeue, types};
+
+/// `io_uring_sqe` as laid out by the kernel ABI, with the unions of the fields
+/// interpreted by the in-process drivers named after their use.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct Sqe {
+    pub(crate) opcode: u8,
+    pub(crate) flags: u8,
+    _ioprio: u16,
+    pub(crate) fd: i32,
+    pub(crate) off: u64,
+    pub(crate) addr: u64,
+    pub(crate) len: u32,
+    pub(crate) op_flags: u32,
+    _user_data: u64,
+    _buf_index: u16,
+    _personality: u16,
+    pub(crate) file_index: u32,
+    _addr3: u64,
+    _pad: u64,
+}
+
+const _: () = assert!(size_of::<Sqe>() == size_of::<squeue::Entry>());
+
+// Safety: the addresses an SQE carries stay valid until its op completes, on
+// whichever thread it is performed, just like for the kernel.
+unsafe impl Send for Sqe {}
+
+impl Sqe {
+    pub(crate) fn new(entry: squeue::Entry) -> Self {
+        // Safety: `Entry` is a `repr(C)` wrapper of `io_uring_sqe`.
+        unsafe { std::mem::transmute::<squeue::Entry, Sqe>(entry) }
+    }
+
+    pub(crate) fn into_entry(self) -> squeue::Entry {
+        // Safety: see `new`.
+        unsafe { std::mem::transmute::<Sqe, squeue::Entry>(self) }
+    }
+
+    /// Features only io_uring itself provides.
+    pub(crate) fn unsupported(&self) -> bool {
+        let flags = squeue::Flags::from_bits_retain(self.flags);
+        flags.intersects(squeue::Flags::FIXED_FILE | squeue::Flags::BUFFER_SELECT)
+            || (matches!(
+                self.opcode,
+                opcode::Accept::CODE
+                    | opcode::OpenAt::CODE
+                    | opcode::OpenAt2::CODE
+                    | opcode::Close::CODE
+                    | opcode::Socket::CODE
+            ) && self.file_index != 0)
+    }
+
+    /// Whether a soft link of a chain is broken by `result`, like the kernel does
+    /// for failed and short reads/writes.
+    pub(crate) fn breaks_link(&self, result: i32) -> bool {
+        let flags = squeue::Flags::from_bits_retain(self.flags);
+        if !flags.contains(squeue::Flags::IO_LINK) || flags.contains(squeue::Flags::IO_HARDLINK) {
+            return false;
+        }
+        let rw = matches!(
+            self.opcode,
+            opcode::Read::CODE
+                | opcode::Write::CODE
+                | opcode::ReadFixed::CODE
+                | opcode::WriteFixed::CODE
+                | opcode::Recv::CODE
+                | opcode::Send::CODE
+        );
+        result < 0 || (rw && (result as u32) < self.len)
+    }
+
+    /// Whether `perform` knows the opcode.
+    pub(crate) fn performable(opcode: u8) -> bool {
+        matches!(
+            opcode,
+            opcode::Read::CODE
+                | opcode::ReadFixed::CODE
+                | opcode::Write::CODE
+                | opcode::WriteFixed::CODE
+                | opcode::Recv::CODE
+                | opcode::Send::CODE
+                | opcode::Accept::CODE
+                | opcode::Connect::CODE
+                | opcode::OpenAt::CODE
+                | opcode::OpenAt2::CODE
+                | opcode::Close::CODE
+                | opcode::Socket::CODE
+                | opcode::Readv::CODE
+                | opcode::Writev::CODE
+                | opcode::SendMsg::CODE
+                | opcode::RecvMsg::CODE
+                | opcode::Statx::CODE
+                | opcode::Fsync::CODE
+                | opcode::SyncFileRange::CODE
+                | opcode::Fallocate::CODE
+                | opcode::Ftruncate::CODE
+        )
+    }
+
+    /// Whether the op transfers bytes, so that a `Fault::Short` can cut it down.
+    pub(crate) fn shortenable(&self) -> bool {
+        matches!(
+            self.opcode,
+            opcode::Read::CODE
+                | opcode::Write::CODE
+                | opcode::ReadFixed::CODE
+                | opcode::WriteFixed::CODE
+                | opcode::Recv::CODE
+                | opcode::Send::CODE
+                | opcode::Readv::CODE
+                | opcode::Writev::CODE
+                | opcode::SendMsg::CODE
+                | opcode::RecvMsg::CODE
+        )
+    }
+
+    /// Whether a successful result is a new fd, rather than a fixed slot.
+    pub(crate) fn creates_fd(&self) -> bool {
+        matches!(
+            self.opcode,
+            opcode::Accept::CODE
+                | opcode::OpenAt::CODE
+                | opcode::OpenAt2::CODE
+                | opcode::Socket::CODE
+        ) && self.file_index == 0
+    }
+
+    /// Cut a `shortenable` op down to at most `max` bytes. Vectored ops are pointed
+    /// at a clipped copy of their iovecs, which has to be kept until they complete.
+    pub(crate) fn shorten(&mut self, max: u32) -> Option<Shortened> {
+        let vectored = matches!(
+            self.opcode,
+            opcode::Readv::CODE
+                | opcode::Writev::CODE
+                | opcode::SendMsg::CODE
+                | opcode::RecvMsg::CODE
+        );
+        if !vectored {
+            self.len = self.len.min(max);
+            return None;
+        }
+
+        let mut left = max as usize;
+        let mut iovecs = Vec::new();
+        for iov in self.iovecs() {
+            if left == 0 {
+                break;
+            }
+            let len = iov.iov_len.min(left);
+            iovecs.push(libc::iovec {
+                iov_base: iov.iov_base,
+                iov_len: len,
+            });
+            left -= len;
+        }
+        let mut shortened = Shortened { iovecs, msg: None };
+        if matches!(self.opcode, opcode::Readv::CODE | opcode::Writev::CODE) {
+            self.addr = shortened.iovecs.as_ptr() as u64;
+            self.len = shortened.iovecs.len() as u32;
+        } else {
+            let orig = self.addr as *mut libc::msghdr;
+            // Safety: the op owns its msghdr until it completes.
+            let mut msg = Box::new(unsafe { *orig });
+            msg.msg_iov = shortened.iovecs.as_mut_ptr();
+            msg.msg_iovlen = shortened.iovecs.len();
+            self.addr = &*msg as *const libc::msghdr as u64;
+            shortened.msg = Some((msg, orig));
+        }
+        Some(shortened)
+    }
+
+    /// The buffers of a read or write op, one iovec for the non vectored ones.
+    pub(crate) fn iovecs(&self) -> Vec<libc::iovec> {
+        // Safety: the op owns its iovecs and msghdr until it completes.
+        unsafe {
+            match self.opcode {
+                opcode::Readv::CODE | opcode::Writev::CODE => {
+                    std::slice::from_raw_parts(self.addr as *const libc::iovec, self.len as usize)
+                        .to_vec()
+                }
+                opcode::SendMsg::CODE | opcode::RecvMsg::CODE => {
+                    let msg = &*(self.addr as *const libc::msghdr);
+                    std::slice::from_raw_parts(msg.msg_iov, msg.msg_iovlen).to_vec()
+                }
+                _ => vec![libc::iovec {
+                    iov_base: self.addr as *mut libc::c_void,
+                    iov_len: self.len as usize,
+                }],
+            }
+        }
+    }
+
+    /// Perform the op with a blocking syscall.
+    pub(crate) fn perform(&self) -> i32 {
+        if self.unsupported() {
+            return -libc::EOPNOTSUPP;
+        }
+        let fd = self.fd;
+        let buf = self.addr as *mut libc::c_void;
+        let len = self.len as usize;
+        let ret = unsafe {
+            match self.opcode {
+                opcode::Read::CODE | opcode::ReadFixed::CODE if self.off == u64::MAX => {
+                    libc::read(fd, buf, len) as i64
+                }
+                opcode::Read::CODE | opcode::ReadFixed::CODE => {
+                    libc::pread(fd, buf, len, self.off as i64) as i64
+                }
+                opcode::Write::CODE | opcode::WriteFixed::CODE if self.off == u64::MAX => {
+                    libc::write(fd, buf, len) as i64
+                }
+                opcode::Write::CODE | opcode::WriteFixed::CODE => {
+                    libc::pwrite(fd, buf, len, self.off as i64) as i64
+                }
+                opcode::Recv::CODE => libc::recv(fd, buf, len, self.op_flags as i32) as i64,
+                opcode::Send::CODE => {
+                    libc::send(fd, buf, len, self.op_flags as i32 | libc::MSG_NOSIGNAL) as i64
+                }
+                opcode::Accept::CODE => libc::accept4(
+                    fd,
+                    self.addr as *mut libc::sockaddr,
+                    self.off as *mut libc::socklen_t,
+                    self.op_flags as i32,
+                ) as i64,
+                opcode::Connect::CODE => libc::connect(
+                    fd,
+                    self.addr as *const libc::sockaddr,
+                    self.off as libc::socklen_t,
+                ) as i64,
+                opcode::OpenAt::CODE => libc::openat(
+                    fd,
+                    self.addr as *const libc::c_char,
+                    self.op_flags as i32,
+                    self.len as libc::mode_t,
+                ) as i64,
+                opcode::OpenAt2::CODE => libc::syscall(
+                    libc::SYS_openat2,
+                    fd,
+                    self.addr as *const libc::c_char,
+                    self.off as *const libc::open_how,
+                    self.len as usize,
+                ),
+                opcode::Close::CODE => libc::close(fd) as i64,
+                opcode::Socket::CODE => libc::socket(fd, self.off as i32, self.len as i32) as i64,
+                opcode::Readv::CODE if self.off == u64::MAX => {
+                    libc::readv(fd, self.addr as *const libc::iovec, self.len as i32) as i64
+                }
+                opcode::Readv::CODE => libc::preadv(
+                    fd,
+                    self.addr as *const libc::iovec,
+                    self.len as i32,
+                    self.off as i64,
+                ) as i64,
+                opcode::Writev::CODE if self.off == u64::MAX => {
+                    libc::writev(fd, self.addr as *const libc::iovec, self.len as i32) as i64
+                }
+                opcode::Writev::CODE => libc::pwritev(
+                    fd,
+                    self.addr as *const libc::iovec,
+                    self.len as i32,
+                    self.off as i64,
+                ) as i64,
+                opcode::SendMsg::CODE => libc::sendmsg(
+                    fd,
+                    self.addr as *const libc::msghdr,
+                    self.op_flags as i32 | libc::MSG_NOSIGNAL,
+                ) as i64,
+                opcode::RecvMsg::CODE => {
+                    libc::recvmsg(fd, self.addr as *mut libc::msghdr, self.op_flags as i32) as i64
+                }
+                opcode::Statx::CODE => libc::statx(
+                    fd,
+                    self.addr as *const libc::c_char,
+                    self.op_flags as i32,
+                    self.len,
+                    self.off as *mut libc::statx,
+                ) as i64,
+                opcode::Fsync::CODE if self.op_flags & types::FsyncFlags::DATASYNC.bits() != 0 => {
+                    libc::fdatasync(fd) as i64
+                }
+                opcode::Fsync::CODE => libc::fsync(fd) as i64,
+                opcode::SyncFileRange::CODE => {
+                    libc::sync_file_range(fd, self.off as i64, self.len as i64, self.op_flags)
+                        as i64
+                }
+                opcode::Fallocate::CODE => {
+                    libc::fallocate(fd, self.len as i32, self.off as i64, self.addr as i64) as i64
+                }
+                opcode::Ftruncate::CODE => libc::ftruncate(fd, self.off as i64) as i64,
+                _ => return -libc::EOPNOTSUPP,
+            }
+        };
+        syscall_result(ret)
+    }
+}
+
+/// The clipped iovecs (and msghdr) a shortened vectored op points to.
+pub(crate) struct Shortened {
+    iovecs: Vec<libc::iovec>,
+    // the copy the op runs on, and the op's own msghdr
+    msg: Option<(Box<libc::msghdr>, *mut libc::msghdr)>,
+}
+
+impl Shortened {
+    /// Once the op completed, hand what `recvmsg` reports in the msghdr over to the
+    /// op's own.
+    pub(crate) fn finish(self) {
+        if let Some((msg, orig)) = self.msg {
+            // Safety: the op has not seen its completion yet, so it still owns `orig`.
+            unsafe {
+                (*orig).msg_namelen = msg.msg_namelen;
+                (*orig).msg_controllen = msg.msg_controllen;
+                (*orig).msg_flags = msg.msg_flags;
+            }
+        }
+    }
+}
+
+pub(crate) fn syscall_result(ret: i64) -> i32 {
+    if ret < 0 {
+        -io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or(libc::EIO)
+    } else {
+        ret as i32
+    }
+}
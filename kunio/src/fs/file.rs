@@ -1,66 +1,434 @@
 use std::io;
-use std::os::fd::RawFd;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
 use std::path::Path;
 
-use crate::buf::{IoBuf, IoBufMut};
-use crate::driver::op::Op;
+use crate::buf::{BufResult, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use crate::driver::fd::{FixedFd, UringFd};
+use crate::driver::op::{Op, check_iovecs_len, check_rw_len};
+use crate::fs::OpenOptions;
+use crate::fs::metadata::{self, Metadata};
+use crate::io_util::{self, ReadOwned, WriteOwned};
+use crate::runtime::RUNTIME;
+
+/// The ops `File` and `FixedFile` share, issued on `self.fd`.
+macro_rules! file_io {
+    () => {
+            /// Close the file, reporting the error `close(2)` gives, e.g. a failed writeback.
+            /// Dropping the file closes it too, but the error is lost.
+            pub async fn close(self) -> io::Result<()> {
+                let fd = UringFd::from(self.fd);
+                std::mem::forget(self);
+                fd.close().await
+            }
+
+            pub async fn write<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+                if let Err(e) = self.check_buf(buf.stable_ptr(), buf.bytes_init(), None) {
+                    return (Err(e), buf);
+                }
+                let completion = Op::write(self.fd, buf).await;
+                (completion.result.map(|n| n as usize), completion.data.buf)
+            }
+
+            pub async fn read<T: IoBufMut>(&self, mut buf: T) -> BufResult<usize, T> {
+                if let Err(e) = self.check_read(&mut buf, None) {
+                    return (Err(e), buf);
+                }
+                let mut completion = Op::read(self.fd, buf).await;
+                if let Ok(n) = completion.result {
+                    // Safety: the kernel initialized `n` bytes after `bytes_init`.
+                    let init = completion.data.buf.bytes_init();
+                    unsafe { completion.data.buf.set_init(init + n as usize) };
+                }
+                (completion.result.map(|n| n as usize), completion.data.buf)
+            }
+
+            pub async fn write_at<T: IoBuf>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+                if let Err(e) = self.check_buf(buf.stable_ptr(), buf.bytes_init(), Some(pos)) {
+                    return (Err(e), buf);
+                }
+                let completion = Op::write_at(self.fd, buf, pos).await;
+                (completion.result.map(|n| n as usize), completion.data.buf)
+            }
+
+            pub async fn read_at<T: IoBufMut>(&self, mut buf: T, pos: u64) -> BufResult<usize, T> {
+                if let Err(e) = self.check_read(&mut buf, Some(pos)) {
+                    return (Err(e), buf);
+                }
+                let mut completion = Op::read_at(self.fd, buf, pos).await;
+                if let Ok(n) = completion.result {
+                    // Safety: the kernel initialized `n` bytes after `bytes_init`.
+                    let init = completion.data.buf.bytes_init();
+                    unsafe { completion.data.buf.set_init(init + n as usize) };
+                }
+                (completion.result.map(|n| n as usize), completion.data.buf)
+            }
+
+            /// Write several buffers in one op, at the file position.
+            pub async fn write_vectored<T: IoVecBuf>(&self, bufs: T) -> BufResult<usize, T> {
+                if let Err(e) = self.check_iovecs(&bufs.read_iovecs(), None) {
+                    return (Err(e), bufs);
+                }
+                let completion = Op::writev(self.fd, bufs).await;
+                (completion.result.map(|n| n as usize), completion.data.bufs)
+            }
+
+            /// Read into several buffers in one op, filling them in order.
+            pub async fn read_vectored<T: IoVecBufMut>(&self, mut bufs: T) -> BufResult<usize, T> {
+                if let Err(e) = self.check_iovecs(&bufs.write_iovecs(), None) {
+                    return (Err(e), bufs);
+                }
+                let mut completion = Op::readv(self.fd, bufs).await;
+                if let Ok(n) = completion.result {
+                    // Safety: the kernel initialized the first `n` bytes of the iovecs.
+                    unsafe { completion.data.bufs.advance_init(n as usize) };
+                }
+                (completion.result.map(|n| n as usize), completion.data.bufs)
+            }
+
+            pub async fn write_vectored_at<T: IoVecBuf>(&self, bufs: T, pos: u64) -> BufResult<usize, T> {
+                if let Err(e) = self.check_iovecs(&bufs.read_iovecs(), Some(pos)) {
+                    return (Err(e), bufs);
+                }
+                let completion = Op::writev_at(self.fd, bufs, pos).await;
+                (completion.result.map(|n| n as usize), completion.data.bufs)
+            }
+
+            pub async fn read_vectored_at<T: IoVecBufMut>(
+                &self,
+                mut bufs: T,
+                pos: u64,
+            ) -> BufResult<usize, T> {
+                if let Err(e) = self.check_iovecs(&bufs.write_iovecs(), Some(pos)) {
+                    return (Err(e), bufs);
+                }
+                let mut completion = Op::readv_at(self.fd, bufs, pos).await;
+                if let Ok(n) = completion.result {
+                    // Safety: the kernel initialized the first `n` bytes of the iovecs.
+                    unsafe { completion.data.bufs.advance_init(n as usize) };
+                }
+                (completion.result.map(|n| n as usize), completion.data.bufs)
+            }
+
+            /// Flush the file's data and metadata to the device.
+            pub async fn sync_all(&self) -> io::Result<()> {
+                Op::fsync(self.fd).await.result.map(|_| ())
+            }
+
+            /// Flush the file's data, and of its metadata only what is needed to read the
+            /// data back, e.g. the size but not the modification time.
+            pub async fn sync_data(&self) -> io::Result<()> {
+                Op::datasync(self.fd).await.result.map(|_| ())
+            }
+
+            /// `sync_file_range(2)` of `len` bytes at `offset`, a `len` of 0 meaning up to
+            /// the end of the file. `flags` are `libc::SYNC_FILE_RANGE_*`. Unlike
+            /// `sync_data`, this does not flush metadata or the device's write cache.
+            pub async fn sync_range(&self, mut offset: u64, mut len: u64, flags: u32) -> io::Result<()> {
+                // The SQE length is 32 bits, sync longer ranges piece by piece.
+                while len > u32::MAX as u64 {
+                    // The largest page-aligned length.
+                    let chunk: u32 = !4095;
+                    Op::sync_range(self.fd, offset, chunk, flags).await.result?;
+                    offset += chunk as u64;
+                    len -= chunk as u64;
+                }
+                Op::sync_range(self.fd, offset, len as u32, flags)
+                    .await
+                    .result
+                    .map(|_| ())
+            }
+
+            /// Allocate disk space for `len` bytes at `offset`, growing the file if they
+            /// reach past its end, so later writes there do not fail with `ENOSPC`.
+            pub async fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+                Op::fallocate(self.fd, offset, len, 0)
+                    .await
+                    .result
+                    .map(|_| ())
+            }
+
+            /// Free the disk space of `len` bytes at `offset`, which read back as zeros. The
+            /// file size stays the same.
+            pub async fn punch_hole(&self, offset: u64, len: u64) -> io::Result<()> {
+                let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+                Op::fallocate(self.fd, offset, len, mode)
+                    .await
+                    .result
+                    .map(|_| ())
+            }
+
+            /// Truncate or zero-extend the file to `len` bytes.
+            pub async fn set_len(&self, len: u64) -> io::Result<()> {
+                Op::ftruncate(self.fd, len).await.result.map(|_| ())
+            }
+
+            /// Write the whole buffer, issuing more writes after short ones.
+            ///
+            /// Fails with `InvalidInput` on an `O_DIRECT` file, as does the rest of
+            /// these loops: after a short transfer they go on at an unaligned offset.
+            pub async fn write_all<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+                if let Err(e) = self.check_not_direct() {
+                    return (Err(e), buf);
+                }
+                io_util::write_all(self, buf).await
+            }
+
+            /// Fill the buffer up to `bytes_total`, failing with `UnexpectedEof` if
+            /// the file ends first.
+            pub async fn read_exact<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+                if let Err(e) = self.check_not_direct() {
+                    return (Err(e), buf);
+                }
+                io_util::read_exact(self, buf).await
+            }
+
+            /// Append everything up to end of file to `buf`, returning how much was read.
+            pub async fn read_to_end(&self, buf: Vec<u8>) -> BufResult<usize, Vec<u8>> {
+                if let Err(e) = self.check_not_direct() {
+                    return (Err(e), buf);
+                }
+                io_util::read_to_end(self, buf).await
+            }
+
+            /// Like `read_to_end`, failing with `InvalidData` if what was read is not UTF-8,
+            /// in which case `buf` comes back as it was.
+            pub async fn read_to_string(&self, buf: String) -> BufResult<usize, String> {
+                if let Err(e) = self.check_not_direct() {
+                    return (Err(e), buf);
+                }
+                io_util::read_to_string(self, buf).await
+            }
+
+            fn check_not_direct(&self) -> io::Result<()> {
+                if self.direct().is_some() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "O_DIRECT files need aligned transfers, use read_at/write_at",
+                    ));
+                }
+                Ok(())
+            }
+
+            /// Fail early if a transfer of `len` bytes at `ptr` (and `pos`) does not fit
+            /// in one op, or is not aligned to the logical block size of an `O_DIRECT` file.
+            fn check_buf(&self, ptr: *const u8, len: usize, pos: Option<u64>) -> io::Result<()> {
+                check_rw_len(len)?;
+                self.check_direct(ptr, len, pos)
+            }
+
+            fn check_direct(&self, ptr: *const u8, len: usize, pos: Option<u64>) -> io::Result<()> {
+                let Some(block_size) = self.direct() else {
+                    return Ok(());
+                };
+                let block_size = block_size as usize;
+                if !(ptr as usize).is_multiple_of(block_size)
+                    || !len.is_multiple_of(block_size)
+                    || pos.is_some_and(|pos| !pos.is_multiple_of(block_size as u64))
+                {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "O_DIRECT needs the buffer address, length and offset aligned to {block_size} bytes"
+                        ),
+                    ));
+                }
+                Ok(())
+            }
+
+            /// Reads go to the uninitialized part of the buffer.
+            fn check_read<T: IoBufMut>(&self, buf: &mut T, pos: Option<u64>) -> io::Result<()> {
+                let init = buf.bytes_init();
+                // Safety: `bytes_init` is within the buffer.
+                let ptr = unsafe { buf.stable_mut_ptr().add(init) };
+                self.check_buf(ptr, buf.bytes_total() - init, pos)
+            }
+
+            fn check_iovecs(&self, iovecs: &[libc::iovec], pos: Option<u64>) -> io::Result<()> {
+                check_iovecs_len(iovecs)?;
+                for iov in iovecs {
+                    self.check_direct(iov.iov_base as *const u8, iov.iov_len, pos)?;
+                }
+                Ok(())
+            }
+    };
+}
 
 pub struct File {
-    fd: RawFd,
+    pub(super) fd: RawFd,
+    /// The logical block size if opened with `O_DIRECT`.
+    pub(super) direct: Option<u32>,
+}
+
+/// A file in the runtime's registered file table, which ops reach through its
+/// `FixedFd` instead of a regular fd.
+///
+/// It has no descriptor in the process' table, so unlike a `File` it cannot be
+/// handed to code outside the runtime, and has no `statx` based queries.
+pub struct FixedFile {
+    fd: FixedFd,
 }
 
 impl File {
+    /// Open `path` for reading and writing, creating it if needed and truncating it
+    /// otherwise.
     pub async fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
-        let op = Op::open(path, libc::O_CREAT | libc::O_RDWR, 0o644)?;
-        let completion = op.await;
-        Ok(File {
-            fd: completion.result?,
-        })
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .await
     }
 
+    /// Open `path` read-only.
     pub async fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
-        let op = Op::open(path, libc::O_RDWR, 0o644)?;
-        let completion = op.await;
-        Ok(File {
-            fd: completion.result?,
-        })
+        OpenOptions::new().read(true).open(path).await
     }
 
-    pub async fn close(&self) -> io::Result<()> {
-        Op::close(self.fd)?.await;
-        Ok(())
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
     }
 
-    pub async fn write<T: IoBuf>(&self, buf: T) -> io::Result<(usize, T)> {
-        let op = Op::write(self.fd, buf)?;
-        let completion = op.await;
-        Ok((completion.result? as usize, completion.data.buf))
+    pub fn as_uring_fd(&self) -> UringFd {
+        UringFd::Raw(self.fd)
     }
 
-    pub async fn read<T: IoBufMut>(&self, buf: T) -> io::Result<(usize, T)> {
-        let op = Op::read(self.fd, buf)?;
-        let mut completion = op.await;
-        let result = completion.result?;
-        // Safety:
-        unsafe {
-            completion.data.buf.set_valid_len(result as u32);
+    /// Move the file into the runtime's registered file table, so that later ops
+    /// are issued on the fixed slot. The file is closed if that fails.
+    pub fn into_fixed(self) -> io::Result<FixedFile> {
+        let fd = FixedFd::register(self.into_raw_fd())?;
+        Ok(FixedFile { fd })
+    }
+
+    /// The logical block size of the file's device, which `O_DIRECT` transfers
+    /// have to be aligned to. Fails with `Unsupported` if the kernel does not report
+    /// the direct I/O alignment (before 6.1) and the file is not a block device.
+    pub async fn logical_block_size(&self) -> io::Result<u32> {
+        let statx = metadata::statx(
+            self.fd,
+            Path::new(""),
+            libc::AT_EMPTY_PATH,
+            libc::STATX_DIOALIGN,
+        )
+        .await?
+        .statx;
+        if statx.stx_mask & libc::STATX_DIOALIGN != 0 && statx.stx_dio_offset_align != 0 {
+            return Ok(statx.stx_dio_offset_align.max(statx.stx_dio_mem_align));
+        }
+        if u32::from(statx.stx_mode) & libc::S_IFMT == libc::S_IFBLK {
+            let mut block_size: libc::c_int = 0;
+            // Safety: BLKSSZGET writes one int.
+            if unsafe { libc::ioctl(self.fd, libc::BLKSSZGET, &mut block_size) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            return Ok(block_size as u32);
         }
-        Ok((result as usize, completion.data.buf))
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the kernel does not report the direct I/O alignment of this file",
+        ))
+    }
+
+    /// Size, permissions, times and more of the file.
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        metadata::fd_metadata(self.fd).await
     }
 
-    pub async fn write_at<T: IoBuf>(&self, buf: T, pos: u64) -> io::Result<(usize, T)> {
-        let op = Op::write_at(self.fd, buf, pos)?;
-        let completion = op.await;
-        Ok((completion.result? as usize, completion.data.buf))
+    fn direct(&self) -> Option<u32> {
+        self.direct
     }
 
-    pub async fn read_at<T: IoBufMut>(&self, buf: T, pos: u64) -> io::Result<(usize, T)> {
-        let op = Op::read_at(self.fd, buf, pos)?;
-        let mut completion = op.await;
-        let result = completion.result?;
-        unsafe {
-            completion.data.buf.set_valid_len(result as u32);
+    file_io!();
+}
+
+impl FixedFile {
+    /// Open `path` for reading and writing straight into the runtime's registered
+    /// file table, so the file never gets a regular fd.
+    pub async fn open<P: AsRef<Path>>(path: P) -> io::Result<FixedFile> {
+        let fixed = RUNTIME.with(|runtime| runtime.driver.uring()?.alloc_fixed())?;
+        let completion = Op::open_fixed(path, libc::O_RDWR, 0o644, fixed)?.await;
+        if let Err(e) = completion.result {
+            RUNTIME.with(|runtime| runtime.driver.free_fixed(fixed));
+            return Err(e);
         }
-        Ok((result as usize, completion.data.buf))
+        Ok(FixedFile { fd: fixed })
+    }
+
+    pub fn from_fixed(fd: FixedFd) -> FixedFile {
+        FixedFile { fd }
+    }
+
+    pub fn as_uring_fd(&self) -> UringFd {
+        UringFd::Fixed(self.fd)
+    }
+
+    fn direct(&self) -> Option<u32> {
+        None
+    }
+
+    file_io!();
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        UringFd::Raw(self.fd).close_detached();
+    }
+}
+
+impl Drop for FixedFile {
+    fn drop(&mut self) {
+        UringFd::Fixed(self.fd).close_detached();
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for File {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the fd stays open as long as `self`.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl IntoRawFd for File {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        std::mem::forget(self);
+        fd
+    }
+}
+
+impl FromRawFd for File {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        File { fd, direct: None }
+    }
+}
+
+impl ReadOwned for File {
+    async fn read_owned<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.read(buf).await
+    }
+}
+
+impl WriteOwned for File {
+    async fn write_owned<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.write(buf).await
+    }
+}
+
+impl ReadOwned for FixedFile {
+    async fn read_owned<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.read(buf).await
+    }
+}
+
+impl WriteOwned for FixedFile {
+    async fn write_owned<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.write(buf).await
     }
 }
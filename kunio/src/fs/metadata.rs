@@ -0,0 +1,181 @@
+use std::fs::Permissions;
+use std::io;
+use std::os::fd::RawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::driver::op::Op;
+
+/// What `statx(2)` is asked for. The kernel may leave out fields a filesystem does
+/// not have, e.g. the creation time.
+const STATX_MASK: u32 = libc::STATX_BASIC_STATS | libc::STATX_BTIME;
+
+/// Metadata of a file, like `std::fs::Metadata`.
+#[derive(Clone)]
+pub struct Metadata {
+    pub(super) statx: libc::statx,
+}
+
+impl Metadata {
+    pub fn file_type(&self) -> FileType {
+        FileType {
+            mode: self.mode() & libc::S_IFMT,
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+
+    /// Size in bytes.
+    pub fn len(&self) -> u64 {
+        self.statx.stx_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_mode(self.mode() & 0o7777)
+    }
+
+    /// The `st_mode` bits, file type included.
+    pub fn mode(&self) -> u32 {
+        self.statx.stx_mode as u32
+    }
+
+    pub fn ino(&self) -> u64 {
+        self.statx.stx_ino
+    }
+
+    /// The device the file lives on.
+    pub fn dev(&self) -> u64 {
+        libc::makedev(self.statx.stx_dev_major, self.statx.stx_dev_minor)
+    }
+
+    pub fn nlink(&self) -> u64 {
+        self.statx.stx_nlink as u64
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.statx.stx_uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.statx.stx_gid
+    }
+
+    /// The preferred size of an I/O, see `File::logical_block_size` for `O_DIRECT`.
+    pub fn blksize(&self) -> u64 {
+        self.statx.stx_blksize as u64
+    }
+
+    /// Allocated 512 byte blocks.
+    pub fn blocks(&self) -> u64 {
+        self.statx.stx_blocks
+    }
+
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.time(libc::STATX_MTIME, self.statx.stx_mtime, "modification")
+    }
+
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        self.time(libc::STATX_ATIME, self.statx.stx_atime, "access")
+    }
+
+    /// Fails with `Unsupported` on filesystems that do not record it.
+    pub fn created(&self) -> io::Result<SystemTime> {
+        self.time(libc::STATX_BTIME, self.statx.stx_btime, "creation")
+    }
+
+    fn time(&self, mask: u32, time: libc::statx_timestamp, what: &str) -> io::Result<SystemTime> {
+        if self.statx.stx_mask & mask == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{what} time is not available"),
+            ));
+        }
+        let since_epoch = Duration::new(time.tv_sec.unsigned_abs(), time.tv_nsec);
+        if time.tv_sec < 0 {
+            Ok(SystemTime::UNIX_EPOCH - since_epoch)
+        } else {
+            Ok(SystemTime::UNIX_EPOCH + since_epoch)
+        }
+    }
+}
+
+impl std::fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metadata")
+            .field("file_type", &self.file_type())
+            .field("len", &self.len())
+            .field("permissions", &self.permissions())
+            .field("ino", &self.ino())
+            .field("modified", &self.modified().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The type of a file, like `std::fs::FileType`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FileType {
+    mode: u32,
+}
+
+impl FileType {
+    pub fn is_file(&self) -> bool {
+        self.mode == libc::S_IFREG
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode == libc::S_IFDIR
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode == libc::S_IFLNK
+    }
+}
+
+/// Metadata of the file at `path`, following symlinks.
+pub async fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    statx(libc::AT_FDCWD, path.as_ref(), 0, STATX_MASK).await
+}
+
+/// Like `metadata`, but of the symlink itself if `path` is one.
+pub async fn symlink_metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    statx(
+        libc::AT_FDCWD,
+        path.as_ref(),
+        libc::AT_SYMLINK_NOFOLLOW,
+        STATX_MASK,
+    )
+    .await
+}
+
+/// Metadata of an open file.
+pub(super) async fn fd_metadata(fd: RawFd) -> io::Result<Metadata> {
+    statx(fd, Path::new(""), libc::AT_EMPTY_PATH, STATX_MASK).await
+}
+
+pub(super) async fn statx(
+    dirfd: RawFd,
+    path: &Path,
+    flags: i32,
+    mask: u32,
+) -> io::Result<Metadata> {
+    let completion = Op::statx(dirfd, path, flags, mask)?.await;
+    completion.result?;
+    Ok(Metadata {
+        statx: *completion.data.statx,
+    })
+}
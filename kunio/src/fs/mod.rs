@@ -1,3 +1,7 @@
 pub mod file;
+mod metadata;
+mod open_options;
 
-pub use file::File;
+pub use file::{File, FixedFile};
+pub use metadata::{FileType, Metadata, metadata, symlink_metadata};
+pub use open_options::OpenOptions;
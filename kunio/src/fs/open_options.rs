@@ -0,0 +1,260 @@
+use std::io;
+use std::path::Path;
+
+use crate::driver::op::Op;
+use crate::fs::File;
+
+/// Options to open a `File` with, like `std::fs::OpenOptions`.
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    direct: bool,
+    mode: libc::mode_t,
+    custom_flags: i32,
+    resolve: u64,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            direct: false,
+            mode: 0o666,
+            custom_flags: 0,
+            resolve: 0,
+        }
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Fail with `AlreadyExists` if the file exists, implies `create`.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Open with `O_DIRECT`, bypassing the page cache. Reads and writes on the file
+    /// then fail with `InvalidInput` unless the buffer address, the length and the
+    /// offset are multiples of the logical block size, see `AlignedBuf`. The
+    /// `write_all`/`read_exact` style loops are refused, and opening fails with
+    /// `Unsupported` where the alignment cannot be learned.
+    pub fn direct(&mut self, direct: bool) -> &mut Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Permissions of a newly created file, before the umask. `0o666` by default.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode as libc::mode_t;
+        self
+    }
+
+    /// Extra `O_*` flags such as `O_NOATIME` or `O_SYNC`, the access mode bits are
+    /// ignored. `O_CLOEXEC` is always set.
+    pub fn custom_flags(&mut self, flags: i32) -> &mut Self {
+        self.custom_flags = flags;
+        self
+    }
+
+    /// Restrict how the path is resolved with `RESOLVE_*` flags, e.g.
+    /// `libc::RESOLVE_BENEATH` to stay below the working directory. The file is then
+    /// opened with `openat2`, which needs Linux 5.6.
+    pub fn resolve(&mut self, resolve: u64) -> &mut Self {
+        self.resolve = resolve;
+        self
+    }
+
+    pub async fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        let op = if self.resolve == 0 {
+            Op::open(path, self.flags()?, self.mode)?
+        } else {
+            Op::openat2(path, self.flags()?, self.mode, self.resolve)?
+        };
+        let completion = op.await;
+        let mut file = File {
+            fd: completion.result?,
+            direct: None,
+        };
+        if self.direct {
+            // Learn the alignment now, so misaligned ops fail before reaching the kernel.
+            match file.logical_block_size().await {
+                Ok(block_size) => file.direct = Some(block_size),
+                Err(e) => {
+                    let _ = file.close().await;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(file)
+    }
+
+    fn flags(&self) -> io::Result<i32> {
+        let access = match (self.read, self.write || self.append) {
+            (true, false) => libc::O_RDONLY,
+            (false, true) => libc::O_WRONLY,
+            (true, true) => libc::O_RDWR,
+            (false, false) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "file must be opened for reading or writing",
+                ));
+            }
+        };
+        if (self.truncate || self.create || self.create_new) && !(self.write || self.append) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "creating or truncating a file needs write access",
+            ));
+        }
+        if self.truncate && self.append && !self.create_new {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a file cannot be truncated and appended to",
+            ));
+        }
+
+        let mut flags = access | libc::O_CLOEXEC | (self.custom_flags & !libc::O_ACCMODE);
+        if self.append {
+            flags |= libc::O_APPEND;
+        }
+        if self.direct {
+            flags |= libc::O_DIRECT;
+        }
+        flags |= match (self.create, self.truncate, self.create_new) {
+            (_, _, true) => libc::O_CREAT | libc::O_EXCL,
+            (true, true, false) => libc::O_CREAT | libc::O_TRUNC,
+            (true, false, false) => libc::O_CREAT,
+            (false, true, false) => libc::O_TRUNC,
+            (false, false, false) => 0,
+        };
+        Ok(flags)
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenOptions;
+
+    fn flags(options: &mut OpenOptions) -> Option<i32> {
+        options.flags().ok().map(|flags| flags & !libc::O_CLOEXEC)
+    }
+
+    #[test]
+    fn access_mode() {
+        assert_eq!(flags(&mut OpenOptions::new()), None);
+        assert_eq!(flags(OpenOptions::new().read(true)), Some(libc::O_RDONLY));
+        assert_eq!(flags(OpenOptions::new().write(true)), Some(libc::O_WRONLY));
+        assert_eq!(
+            flags(OpenOptions::new().read(true).write(true)),
+            Some(libc::O_RDWR)
+        );
+        assert_eq!(
+            flags(OpenOptions::new().append(true)),
+            Some(libc::O_WRONLY | libc::O_APPEND)
+        );
+    }
+
+    #[test]
+    fn create_and_truncate() {
+        assert_eq!(flags(OpenOptions::new().read(true).create(true)), None);
+        assert_eq!(flags(OpenOptions::new().read(true).truncate(true)), None);
+        assert_eq!(flags(OpenOptions::new().read(true).create_new(true)), None);
+        assert_eq!(
+            flags(OpenOptions::new().write(true).create(true)),
+            Some(libc::O_WRONLY | libc::O_CREAT)
+        );
+        assert_eq!(
+            flags(OpenOptions::new().write(true).truncate(true)),
+            Some(libc::O_WRONLY | libc::O_TRUNC)
+        );
+        assert_eq!(
+            flags(OpenOptions::new().write(true).create(true).truncate(true)),
+            Some(libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC)
+        );
+        // `create_new` wins over `create` and `truncate`.
+        assert_eq!(
+            flags(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .create_new(true)
+            ),
+            Some(libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL)
+        );
+    }
+
+    #[test]
+    fn truncate_and_append() {
+        assert_eq!(flags(OpenOptions::new().append(true).truncate(true)), None);
+        assert_eq!(
+            flags(
+                OpenOptions::new()
+                    .append(true)
+                    .truncate(true)
+                    .create_new(true)
+            ),
+            Some(libc::O_WRONLY | libc::O_APPEND | libc::O_CREAT | libc::O_EXCL)
+        );
+    }
+
+    #[test]
+    fn extra_flags() {
+        assert_eq!(
+            flags(OpenOptions::new().read(true).direct(true)),
+            Some(libc::O_RDONLY | libc::O_DIRECT)
+        );
+        // The access mode comes from `read`/`write` only.
+        assert_eq!(
+            flags(
+                OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_RDWR | libc::O_NOFOLLOW)
+            ),
+            Some(libc::O_RDONLY | libc::O_NOFOLLOW)
+        );
+        assert_ne!(
+            OpenOptions::new().read(true).flags().unwrap() & libc::O_CLOEXEC,
+            0
+        );
+    }
+}
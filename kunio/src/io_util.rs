@@ -0,0 +1,102 @@
+//! Loops over short reads and writes shared by `TcpStream` and `File`.
+
+use std::io;
+
+use crate::buf::{BufResult, IoBuf, IoBufMut};
+use crate::driver::op::MAX_RW_COUNT;
+
+// what `read_to_end` reserves whenever the buffer is full
+const READ_TO_END_CHUNK: usize = 8 * 1024;
+
+pub(crate) trait ReadOwned {
+    async fn read_owned<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T>;
+}
+
+pub(crate) trait WriteOwned {
+    async fn write_owned<T: IoBuf>(&self, buf: T) -> BufResult<usize, T>;
+}
+
+pub(crate) async fn write_all<W: WriteOwned, T: IoBuf>(w: &W, mut buf: T) -> BufResult<usize, T> {
+    let len = buf.bytes_init();
+    let mut written = 0;
+    while written < len {
+        let end = len.min(written + MAX_RW_COUNT);
+        let (res, slice) = w.write_owned(buf.slice(written..end)).await;
+        buf = slice.into_inner();
+        match res {
+            Ok(0) => {
+                let err = io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                return (Err(err), buf);
+            }
+            Ok(n) => written += n,
+            Err(e) => return (Err(e), buf),
+        }
+    }
+    (Ok(written), buf)
+}
+
+pub(crate) async fn read_exact<R: ReadOwned, T: IoBufMut>(
+    r: &R,
+    mut buf: T,
+) -> BufResult<usize, T> {
+    let mut filled = 0;
+    while buf.bytes_init() < buf.bytes_total() {
+        let res;
+        (res, buf) = read_some(r, buf).await;
+        match res {
+            Ok(0) => {
+                let err =
+                    io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+                return (Err(err), buf);
+            }
+            Ok(n) => filled += n,
+            Err(e) => return (Err(e), buf),
+        }
+    }
+    (Ok(filled), buf)
+}
+
+/// A read into `buf`, limited to what one op can move.
+async fn read_some<R: ReadOwned, T: IoBufMut>(r: &R, buf: T) -> BufResult<usize, T> {
+    let init = buf.bytes_init();
+    let end = buf.bytes_total().min(init + MAX_RW_COUNT);
+    let (res, slice) = r.read_owned(buf.slice(init..end)).await;
+    (res, slice.into_inner())
+}
+
+pub(crate) async fn read_to_end<R: ReadOwned>(
+    r: &R,
+    mut buf: Vec<u8>,
+) -> BufResult<usize, Vec<u8>> {
+    let start = buf.len();
+    loop {
+        if buf.len() == buf.capacity() {
+            buf.reserve(READ_TO_END_CHUNK);
+        }
+        let res;
+        (res, buf) = read_some(r, buf).await;
+        match res {
+            Ok(0) => return (Ok(buf.len() - start), buf),
+            Ok(_) => {}
+            Err(e) => return (Err(e), buf),
+        }
+    }
+}
+
+/// Like `read_to_end`, but only ever hands back valid UTF-8: what was appended is
+/// dropped again if it is not.
+pub(crate) async fn read_to_string<R: ReadOwned>(r: &R, buf: String) -> BufResult<usize, String> {
+    let start = buf.len();
+    let (mut res, mut buf) = read_to_end(r, buf.into_bytes()).await;
+    if std::str::from_utf8(&buf[start..]).is_err() {
+        buf.truncate(start);
+        if res.is_ok() {
+            res = Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            ));
+        }
+    }
+    // Safety: the bytes before `start` came from a `String`, the rest was checked.
+    (res, unsafe { String::from_utf8_unchecked(buf) })
+}
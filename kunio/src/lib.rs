@@ -1,6 +1,7 @@
 pub mod buf;
 pub mod driver;
 pub mod fs;
+mod io_util;
 pub mod net;
 pub mod runtime;
 pub mod scheduler;
@@ -1,12 +1,20 @@
-use std::os::fd::{AsRawFd, RawFd};
+use std::marker::PhantomData;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::{
     io,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs},
 };
 
+use futures_core::Stream;
+
 use crate::{
-    buf::{IoBuf, IoBufMut},
-    driver::op::Op,
+    buf::{BufResult, BufRing, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut, RingBuf},
+    driver::fd::{FixedFd, UringFd},
+    driver::op::{AcceptMulti, MultishotOp, Op, RecvMulti, check_iovecs_len, check_rw_len},
+    io_util::{self, ReadOwned, WriteOwned},
+    runtime::RUNTIME,
 };
 
 pub struct TcpListener {
@@ -17,6 +25,13 @@ pub struct TcpStream {
     fd: RawFd,
 }
 
+/// A connection in the runtime's registered file table, which ops reach through
+/// its `FixedFd` instead of a regular fd. Like a `FixedFile`, it has no descriptor
+/// in the process' table to hand to code outside the runtime.
+pub struct FixedTcpStream {
+    fd: FixedFd,
+}
+
 impl TcpListener {
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
         let listener = std::net::TcpListener::bind(addr)?;
@@ -24,40 +39,198 @@ impl TcpListener {
     }
 
     pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
-        let op = Op::accept(self.listener.as_raw_fd())?;
+        let op = Op::accept(self.listener.as_raw_fd());
         let completion = op.await;
         let stream = TcpStream {
             fd: completion.result?,
         };
-        let storage = completion.data.addr.0.as_ptr();
-        let addr = unsafe {
-            match (*storage).ss_family as _ {
-                libc::AF_INET => {
-                    let addr = *(storage as *const libc::sockaddr_in);
-                    SocketAddr::from(SocketAddrV4::new(
-                        Ipv4Addr::from(addr.sin_addr.s_addr.to_ne_bytes()),
-                        u16::from_be(addr.sin_port),
-                    ))
-                }
-                libc::AF_INET6 => {
-                    let addr = *(storage as *const libc::sockaddr_in6);
-                    SocketAddr::from(SocketAddrV6::new(
-                        Ipv6Addr::from(addr.sin6_addr.s6_addr),
-                        u16::from_be(addr.sin6_port),
-                        addr.sin6_flowinfo,
-                        addr.sin6_scope_id,
-                    ))
+        Ok((stream, peer_addr(completion.data.addr.0.as_ptr())))
+    }
+
+    /// Like `accept`, but the connection is installed directly into the runtime's
+    /// registered file table and never gets a regular fd.
+    pub async fn accept_fixed(&self) -> io::Result<(FixedTcpStream, SocketAddr)> {
+        let fixed = RUNTIME.with(|runtime| runtime.driver.uring()?.alloc_fixed())?;
+        let completion = Op::accept_fixed(self.listener.as_raw_fd(), fixed).await;
+        if let Err(e) = completion.result {
+            RUNTIME.with(|runtime| runtime.driver.free_fixed(fixed));
+            return Err(e);
+        }
+        let stream = FixedTcpStream { fd: fixed };
+        Ok((stream, peer_addr(completion.data.addr.0.as_ptr())))
+    }
+
+    /// A stream of incoming connections, all accepted by a single multishot accept
+    /// SQE. The op is submitted again whenever the kernel terminates it.
+    pub fn incoming(&self) -> io::Result<Incoming<'_>> {
+        let op = MultishotOp::accept_multi(self.listener.as_raw_fd())?;
+        Ok(Incoming {
+            _listener: self,
+            op,
+        })
+    }
+}
+
+pub struct Incoming<'a> {
+    _listener: &'a TcpListener,
+    op: MultishotOp<AcceptMulti>,
+}
+
+impl Stream for Incoming<'_> {
+    type Item = io::Result<TcpStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match this.op.poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some((result, _))) => {
+                    return Poll::Ready(Some(result.map(|fd| TcpStream { fd })));
                 }
-                _ => {
-                    unreachable!()
+                Poll::Ready(None) => {
+                    if let Err(e) = this.op.rearm() {
+                        return Poll::Ready(Some(Err(e)));
+                    }
                 }
             }
-        };
+        }
+    }
+}
 
-        Ok((stream, addr))
+fn peer_addr(storage: *const libc::sockaddr_storage) -> SocketAddr {
+    unsafe {
+        match (*storage).ss_family as _ {
+            libc::AF_INET => {
+                let addr = *(storage as *const libc::sockaddr_in);
+                SocketAddr::from(SocketAddrV4::new(
+                    Ipv4Addr::from(addr.sin_addr.s_addr.to_ne_bytes()),
+                    u16::from_be(addr.sin_port),
+                ))
+            }
+            libc::AF_INET6 => {
+                let addr = *(storage as *const libc::sockaddr_in6);
+                SocketAddr::from(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                ))
+            }
+            _ => {
+                unreachable!()
+            }
+        }
     }
 }
 
+/// The ops `TcpStream` and `FixedTcpStream` share, issued on `self.fd`.
+macro_rules! stream_io {
+    () => {
+        /// Close the socket, reporting the error `close(2)` gives. Dropping the stream
+        /// closes it too, but the error is lost.
+        pub async fn close(self) -> io::Result<()> {
+            let fd = UringFd::from(self.fd);
+            std::mem::forget(self);
+            fd.close().await
+        }
+
+        pub async fn read<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+            if let Err(e) = check_rw_len(buf.bytes_total() - buf.bytes_init()) {
+                return (Err(e), buf);
+            }
+            let mut completion = Op::recv(self.fd, buf).await;
+            if let Ok(n) = completion.result {
+                // Safety: the kernel initialized `n` bytes after `bytes_init`.
+                let init = completion.data.buf.bytes_init();
+                unsafe { completion.data.buf.set_init(init + n as usize) };
+            }
+            (completion.result.map(|n| n as usize), completion.data.buf)
+        }
+
+        /// Receive into a buffer the kernel picks from `ring`, so that an idle connection
+        /// does not pin a buffer while waiting. Returns `None` at end of stream.
+        pub async fn read_ring(&self, ring: &BufRing) -> io::Result<Option<RingBuf>> {
+            let op = Op::recv_ring(self.fd, ring);
+            let completion = op.await;
+            let result = completion.result?;
+            let buf = io_uring::cqueue::buffer_select(completion.flags)
+                // Safety: the kernel handed this buffer id over to us.
+                .map(|bid| unsafe { completion.data.ring.get_buf(bid, result as usize) });
+            Ok(buf.filter(|buf| !buf.is_empty()))
+        }
+
+        /// A stream of received chunks, all produced by a single multishot recv SQE
+        /// picking buffers from `ring`. The stream ends at end of stream or after the
+        /// first error.
+        ///
+        /// The kernel terminates the recv when `ring` runs dry, in which case it is
+        /// submitted again once a `RingBuf` is dropped back into the ring.
+        pub fn recv_stream(&self, ring: &BufRing) -> io::Result<RecvStream<'_>> {
+            let armed_at = ring.recycled();
+            let op = MultishotOp::recv_multi(self.fd, ring)?;
+            Ok(RecvStream {
+                _stream: PhantomData,
+                op,
+                armed_at,
+                starved: false,
+                done: false,
+            })
+        }
+
+        pub async fn write<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+            if let Err(e) = check_rw_len(buf.bytes_init()) {
+                return (Err(e), buf);
+            }
+            let completion = Op::send(self.fd, buf).await;
+            (completion.result.map(|n| n as usize), completion.data.buf)
+        }
+
+        /// Receive into several buffers with one `recvmsg`, filling them in order.
+        pub async fn read_vectored<T: IoVecBufMut>(&self, mut bufs: T) -> BufResult<usize, T> {
+            if let Err(e) = check_iovecs_len(&bufs.write_iovecs()) {
+                return (Err(e), bufs);
+            }
+            let mut completion = Op::recvmsg(self.fd, bufs, 0).await;
+            if let Ok(n) = completion.result {
+                // Safety: the kernel initialized the first `n` bytes of the iovecs.
+                unsafe { completion.data.bufs.advance_init(n as usize) };
+            }
+            (completion.result.map(|n| n as usize), completion.data.bufs)
+        }
+
+        /// Send several buffers, e.g. a header and a body, with one `sendmsg`.
+        pub async fn write_vectored<T: IoVecBuf>(&self, bufs: T) -> BufResult<usize, T> {
+            if let Err(e) = check_iovecs_len(&bufs.read_iovecs()) {
+                return (Err(e), bufs);
+            }
+            let completion = Op::sendmsg(self.fd, bufs, 0).await;
+            (completion.result.map(|n| n as usize), completion.data.bufs)
+        }
+
+        /// Write the whole buffer, issuing more writes after short ones.
+        pub async fn write_all<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+            io_util::write_all(self, buf).await
+        }
+
+        /// Fill the buffer up to `bytes_total`, failing with `UnexpectedEof` if
+        /// the stream ends first.
+        pub async fn read_exact<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+            io_util::read_exact(self, buf).await
+        }
+
+        /// Append everything up to end of stream to `buf`, returning how much was read.
+        pub async fn read_to_end(&self, buf: Vec<u8>) -> BufResult<usize, Vec<u8>> {
+            io_util::read_to_end(self, buf).await
+        }
+
+        /// Like `read_to_end`, failing with `InvalidData` if what was read is not UTF-8,
+        /// in which case `buf` comes back as it was.
+        pub async fn read_to_string(&self, buf: String) -> BufResult<usize, String> {
+            io_util::read_to_string(self, buf).await
+        }
+    };
+}
+
 impl TcpStream {
     pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
         let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
@@ -78,33 +251,202 @@ impl TcpStream {
             ));
         };
 
-        let op = Op::connect(socket, addr)?;
-        let completion = op.await;
-        if completion.result.is_err() {
-            return Err(completion.result.err().unwrap());
-        }
-        return Ok(TcpStream { fd: socket });
+        // Owned right away, so the socket is closed if the connect fails.
+        let stream = TcpStream { fd: socket };
+        Op::connect(socket, addr).await.result?;
+        Ok(stream)
     }
 
-    pub async fn read<T: IoBufMut>(&self, buf: T) -> io::Result<(usize, T)> {
-        let op = Op::recv(self.fd, buf)?;
-        let mut completion = op.await;
-        let result = completion.result?;
-        unsafe {
-            completion.data.buf.set_valid_len(result as u32);
-        }
-        Ok((result as usize, completion.data.buf))
+    pub fn as_uring_fd(&self) -> UringFd {
+        UringFd::Raw(self.fd)
     }
 
-    pub async fn write<T: IoBuf>(&self, buf: T) -> io::Result<(usize, T)> {
-        let op = Op::send(self.fd, buf)?;
-        let completion = op.await;
-        Ok((completion.result? as usize, completion.data.buf))
+    /// Move the socket into the runtime's registered file table, so that later ops
+    /// are issued on the fixed slot. The socket is closed if that fails.
+    pub fn into_fixed(self) -> io::Result<FixedTcpStream> {
+        let fd = FixedFd::register(self.into_raw_fd())?;
+        Ok(FixedTcpStream { fd })
+    }
+
+    stream_io!();
+}
+
+impl FixedTcpStream {
+    /// Like `TcpStream::connect`, but the socket is created directly in the
+    /// runtime's registered file table and never gets a regular fd.
+    pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<FixedTcpStream> {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no address found for the given socket address",
+            )
+        })?;
+        let domain = if addr.is_ipv4() {
+            libc::AF_INET
+        } else {
+            libc::AF_INET6
+        };
+
+        let fixed = if RUNTIME.with(|runtime| runtime.supports(io_uring::opcode::Socket::CODE)) {
+            let fixed = RUNTIME.with(|runtime| runtime.driver.uring()?.alloc_fixed())?;
+            let completion = Op::socket_fixed(domain, libc::SOCK_STREAM, 0, fixed).await;
+            if let Err(e) = completion.result {
+                RUNTIME.with(|runtime| runtime.driver.free_fixed(fixed));
+                return Err(e);
+            }
+            fixed
+        } else {
+            FixedFd::register(socket(domain, libc::SOCK_STREAM, 0).await?)?
+        };
+
+        let stream = FixedTcpStream::from_fixed(fixed);
+        Op::connect(fixed, addr).await.result?;
+        Ok(stream)
+    }
+
+    pub fn from_fixed(fd: FixedFd) -> FixedTcpStream {
+        FixedTcpStream { fd }
+    }
+
+    pub fn as_uring_fd(&self) -> UringFd {
+        UringFd::Fixed(self.fd)
+    }
+
+    stream_io!();
+}
+
+impl ReadOwned for TcpStream {
+    async fn read_owned<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.read(buf).await
     }
 }
 
+impl WriteOwned for TcpStream {
+    async fn write_owned<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.write(buf).await
+    }
+}
+
+impl ReadOwned for FixedTcpStream {
+    async fn read_owned<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.read(buf).await
+    }
+}
+
+impl WriteOwned for FixedTcpStream {
+    async fn write_owned<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.write(buf).await
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        UringFd::Raw(self.fd).close_detached();
+    }
+}
+
+impl Drop for FixedTcpStream {
+    fn drop(&mut self) {
+        UringFd::Fixed(self.fd).close_detached();
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for TcpStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // Safety: the fd stays open as long as `self`.
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+impl IntoRawFd for TcpStream {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        std::mem::forget(self);
+        fd
+    }
+}
+
+impl FromRawFd for TcpStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        TcpStream { fd }
+    }
+}
+
+/// Chunks received on a stream, see `TcpStream::recv_stream`.
+pub struct RecvStream<'a> {
+    _stream: PhantomData<&'a ()>,
+    op: MultishotOp<RecvMulti>,
+    /// `BufRing::recycled` when the recv was last submitted.
+    armed_at: u64,
+    /// The kernel ended the recv because the ring ran dry.
+    starved: bool,
+    done: bool,
+}
+
+impl Stream for RecvStream<'_> {
+    type Item = io::Result<RingBuf>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while !this.done {
+            match this.op.poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some((Ok(len), flags))) => {
+                    let buf = io_uring::cqueue::buffer_select(flags)
+                        // Safety: the kernel handed this buffer id over to us.
+                        .map(|bid| unsafe { this.op.data().ring.get_buf(bid, len as usize) });
+                    match buf {
+                        Some(buf) if !buf.is_empty() => return Poll::Ready(Some(Ok(buf))),
+                        _ => this.done = true,
+                    }
+                }
+                Poll::Ready(Some((Err(e), _))) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    this.starved = true;
+                }
+                Poll::Ready(Some((Err(e), _))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => {
+                    let ring = &this.op.data().ring;
+                    if this.starved {
+                        // Nothing went back to the ring since the recv was armed,
+                        // so it would only fail again.
+                        if ring.recycled() == this.armed_at {
+                            ring.wait_recycle(cx.waker());
+                            return Poll::Pending;
+                        }
+                        this.starved = false;
+                    }
+                    this.armed_at = ring.recycled();
+                    if let Err(e) = this.op.rearm() {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// Create a socket with IORING_OP_SOCKET, or a plain syscall on kernels before 5.19.
 async fn socket(domain: i32, socket_type: i32, protocol: i32) -> io::Result<RawFd> {
-    let op = Op::socket(domain, socket_type, protocol)?;
+    let socket_type = socket_type | libc::SOCK_CLOEXEC;
+    if !RUNTIME.with(|runtime| runtime.supports(io_uring::opcode::Socket::CODE)) {
+        let fd = unsafe { libc::socket(domain, socket_type, protocol) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(fd);
+    }
+    let op = Op::socket(domain, socket_type, protocol);
     let completion = op.await;
     completion.result
 }
@@ -12,8 +12,9 @@ use lazy_static::lazy_static;
 use scoped_tls::scoped_thread_local;
 use threadpool::ThreadPool;
 
-use crate::driver::UringDriver;
-use crate::scheduler::{Schedule, TaskQueue};
+use crate::buf::{BufPool, BufPoolConfig};
+use crate::driver::{Driver, EpollDriver, MockDriver, RuntimeDriver, UringConfig, UringDriver};
+use crate::scheduler::{LocalScheduler, Schedule, TaskQueue};
 use crate::task::{BlockingFuture, JoinHandle, Task, dummy_waker, new_blocking_task, new_task};
 
 lazy_static! {
@@ -47,6 +48,12 @@ impl RuntimeExtCollection {
     }
 }
 
+impl Default for RuntimeExtCollection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct RuntimeExt {
     task_count: AtomicU32,
     woken_tasks: SegQueue<Task>,
@@ -85,31 +92,154 @@ impl RuntimeExt {
     }
 }
 
+impl Default for RuntimeExt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct Runtime {
     pub tasks: TaskQueue,
     pub scheduler: Box<dyn Schedule>,
-    pub driver: UringDriver,
+    pub driver: RuntimeDriver,
     pub threadpool: Option<ThreadPool>,
     pub id: u32,
+    pub buf_pool: BufPool,
 }
 
-impl Runtime {
-    pub fn new(scheduler: Box<dyn Schedule>, attach_thread_size: usize) -> io::Result<Self> {
+/// Which driver a `Runtime` is built on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DriverKind {
+    /// io_uring, falling back to epoll when the kernel does not provide it.
+    #[default]
+    Auto,
+    Uring,
+    Epoll,
+    /// The in-memory `MockDriver`, seeded with `seed`.
+    Mock {
+        seed: u64,
+    },
+}
+
+pub struct RuntimeBuilder {
+    scheduler: Box<dyn Schedule>,
+    attach_thread_size: usize,
+    uring_config: UringConfig,
+    driver: DriverKind,
+    buf_pool: BufPoolConfig,
+}
+
+impl RuntimeBuilder {
+    pub fn new() -> Self {
+        Self {
+            scheduler: Box::new(LocalScheduler),
+            attach_thread_size: 0,
+            uring_config: UringConfig::new(),
+            driver: DriverKind::Auto,
+            buf_pool: BufPoolConfig::new(),
+        }
+    }
+
+    pub fn scheduler(mut self, scheduler: Box<dyn Schedule>) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Number of threads serving `spawn_blocking`, none by default.
+    pub fn attach_thread_size(mut self, attach_thread_size: usize) -> Self {
+        self.attach_thread_size = attach_thread_size;
+        self
+    }
+
+    pub fn uring_config(mut self, config: UringConfig) -> Self {
+        self.uring_config = config;
+        self
+    }
+
+    pub fn driver(mut self, driver: DriverKind) -> Self {
+        self.driver = driver;
+        self
+    }
+
+    /// Layout of the runtime's `BufPool`.
+    pub fn buf_pool(mut self, config: BufPoolConfig) -> Self {
+        self.buf_pool = config;
+        self
+    }
+
+    pub fn build(self) -> io::Result<Runtime> {
+        self.buf_pool.check()?;
+        let driver = match self.driver {
+            DriverKind::Uring => UringDriver::new_with_config(&self.uring_config)?.into(),
+            DriverKind::Epoll => EpollDriver::new()?.into(),
+            DriverKind::Mock { seed } => MockDriver::new(seed).into(),
+            DriverKind::Auto => match UringDriver::new_with_config(&self.uring_config) {
+                Ok(driver) => driver.into(),
+                // io_uring is missing or disabled (e.g. by seccomp or
+                // kernel.io_uring_disabled), other errors are the config's fault.
+                Err(e)
+                    if matches!(
+                        e.raw_os_error(),
+                        Some(libc::ENOSYS | libc::EPERM | libc::EACCES)
+                    ) =>
+                {
+                    EpollDriver::new()?.into()
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        let buf_pool = BufPool::with_driver(&self.buf_pool, &driver)?;
         let id = RUNTIME_IDGEN.fetch_add(1, Ordering::Relaxed);
         RUNTIME_EXT.insert(id, RuntimeExt::new());
 
-        Ok(Self {
+        Ok(Runtime {
             tasks: TaskQueue::new(),
-            scheduler: scheduler,
-            driver: UringDriver::new()?,
-            threadpool: if attach_thread_size == 0 {
+            scheduler: self.scheduler,
+            driver,
+            threadpool: if self.attach_thread_size == 0 {
                 None
             } else {
-                Some(ThreadPool::new(attach_thread_size))
+                Some(ThreadPool::new(self.attach_thread_size))
             },
             id,
+            buf_pool,
         })
     }
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Runtime {
+    pub fn builder() -> RuntimeBuilder {
+        RuntimeBuilder::new()
+    }
+
+    pub fn new(scheduler: Box<dyn Schedule>, attach_thread_size: usize) -> io::Result<Self> {
+        Self::new_with_config(scheduler, attach_thread_size, &UringConfig::new())
+    }
+
+    pub fn new_with_config(
+        scheduler: Box<dyn Schedule>,
+        attach_thread_size: usize,
+        config: &UringConfig,
+    ) -> io::Result<Self> {
+        RuntimeBuilder::new()
+            .scheduler(scheduler)
+            .attach_thread_size(attach_thread_size)
+            .uring_config(config.clone())
+            .build()
+    }
+
+    /// Whether the driver performs ops with this opcode (`opcode::*::CODE`). The
+    /// high-level APIs fall back to plain syscalls for the ones it does not.
+    pub fn supports(&self, opcode: u8) -> bool {
+        self.driver.supports(opcode)
+    }
 
     pub fn block_on<F>(&self, future: F) -> F::Output
     where
@@ -144,13 +274,13 @@ impl Runtime {
                     }
                 }
 
-                if RUNTIME_EXT.get(self.id).unwrap().task_count() == 0 {
-                    if let Poll::Ready(t) = join_handle.as_mut().poll(cx) {
-                        return t;
-                    }
+                if RUNTIME_EXT.get(self.id).unwrap().task_count() == 0
+                    && let Poll::Ready(t) = join_handle.as_mut().poll(cx)
+                {
+                    return t;
                 }
 
-                let _ = self.driver.submit_and_wait();
+                let _ = self.driver.park();
             }
         })
     }
@@ -49,6 +49,16 @@ impl TaskQueue {
         // Safety:
         unsafe { (*self.queue.get()).len() }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for TaskQueue {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub struct LocalScheduler;
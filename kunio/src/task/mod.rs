@@ -94,6 +94,9 @@ impl RawTask {
         unsafe { (vtable.poll)(self.ptr) }
     }
 
+    /// # Safety
+    ///
+    /// `res` must point to a valid `Poll<F::Output>` of the task's future type.
     pub unsafe fn try_read_output(self, res: *mut (), waker: &Waker) {
         let vtable = self.header().vtable;
         // Safety:
@@ -230,10 +233,10 @@ impl Trailer {
     fn set_waker(&self, waker: &Waker) {
         // Safety:
         unsafe {
-            if let Some(join_waker) = &(*self.join_waker.get()) {
-                if join_waker.will_wake(waker) {
-                    return;
-                }
+            if let Some(join_waker) = &(*self.join_waker.get())
+                && join_waker.will_wake(waker)
+            {
+                return;
             }
             *self.join_waker.get() = Some(waker.clone());
         }
@@ -296,7 +299,7 @@ impl<F: Future> TaskHandle<F> {
     fn poll(self) {
         let waker = unsafe { Waker::from_raw(raw_waker::<F>(self.header())) };
         let mut cx = Context::from_waker(&waker);
-        if let Poll::Ready(_) = self.core().poll(&mut cx) {
+        if self.core().poll(&mut cx).is_ready() {
             RUNTIME_EXT
                 .get(self.header().owner_id)
                 .unwrap()
@@ -11,3 +11,9 @@ impl IdGenerator {
         self.0
     }
 }
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}